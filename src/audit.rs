@@ -0,0 +1,202 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+};
+
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use poise::serenity_prelude::{
+    futures::lock::Mutex, ChannelId, CreateAllowedMentions, CreateMessage, Http, User,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::Config;
+
+// Keep mirrored messages and detail columns readable.
+const MAX_DETAIL_LEN: usize = 200;
+// Entries kept in memory for /audit, older ones are only in the file.
+const MAX_RECENT_ENTRIES: usize = 1000;
+
+/// Privileged things the bot does on someone's behalf.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    #[name = "create_account"]
+    CreateAccount,
+    #[name = "link_account"]
+    LinkAccount,
+    #[name = "unlink_account"]
+    UnlinkAccount,
+    #[name = "reset_password"]
+    ResetPassword,
+    #[name = "invite_guest"]
+    InviteGuest,
+    #[name = "delete_guest"]
+    DeleteGuest,
+    #[name = "grant_role"]
+    GrantRole,
+    #[name = "remove_role"]
+    RemoveRole,
+    #[name = "create_ctf"]
+    CreateCtf,
+    #[name = "announce_ctf"]
+    AnnounceCtf,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    #[serde(with = "ts_seconds")]
+    pub time: DateTime<Utc>,
+    /// `None` when the bot acted on its own, e.g. from a background loop.
+    pub actor_id: Option<u64>,
+    pub actor: String,
+    pub action: AuditAction,
+    pub target: String,
+    pub success: bool,
+    pub detail: String,
+}
+
+impl AuditEntry {
+    pub fn new(actor: &User, action: AuditAction, target: impl Into<String>) -> Self {
+        Self {
+            time: Utc::now(),
+            actor_id: Some(actor.id.get()),
+            actor: actor.name.clone(),
+            action,
+            target: target.into(),
+            success: true,
+            detail: String::new(),
+        }
+    }
+
+    pub fn automatic(action: AuditAction, target: impl Into<String>) -> Self {
+        Self {
+            time: Utc::now(),
+            actor_id: None,
+            actor: "automatic".to_string(),
+            action,
+            target: target.into(),
+            success: true,
+            detail: String::new(),
+        }
+    }
+
+    pub fn outcome(mut self, success: bool, detail: impl Into<String>) -> Self {
+        self.success = success;
+        self.detail = detail.into().chars().take(MAX_DETAIL_LEN).collect();
+        self
+    }
+
+    /// One-line Discord summary of the entry.
+    pub fn line(&self) -> String {
+        let actor = self
+            .actor_id
+            .map_or_else(|| self.actor.clone(), |id| format!("<@{id}>"));
+        let mut line = format!(
+            "<t:{}:f> {} **{}** {}: {}",
+            self.time.timestamp(),
+            actor,
+            poise::ChoiceParameter::name(&self.action),
+            self.target,
+            if self.success { "ok" } else { "failed" }
+        );
+        if !self.detail.is_empty() {
+            line.push_str(" - ");
+            line.push_str(&self.detail);
+        }
+        line
+    }
+}
+
+/// Persistent record of privileged bot actions, optionally mirrored to a private channel.
+///
+/// Entries are appended to a JSON lines file so recording one doesn't rewrite the history.
+pub struct AuditLog {
+    file: Mutex<File>,
+    recent: Mutex<VecDeque<AuditEntry>>,
+    channel_id: Option<ChannelId>,
+}
+
+impl AuditLog {
+    pub fn open(config: &Config) -> io::Result<Self> {
+        fs::create_dir_all(&config.data_dir)?;
+        let path = config.data_dir.join("audit_log.jsonl");
+
+        let mut recent = VecDeque::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    match serde_json::from_str(&line) {
+                        Ok(entry) => recent.push_back(entry),
+                        Err(err) => error!("Skipping unreadable audit entry: {:?}", err),
+                    }
+                    if recent.len() > MAX_RECENT_ENTRIES {
+                        recent.pop_front();
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        Ok(Self {
+            file: Mutex::new(OpenOptions::new().create(true).append(true).open(path)?),
+            recent: Mutex::new(recent),
+            channel_id: config.audit_channel_id.map(ChannelId::new),
+        })
+    }
+
+    async fn append(&self, entry: &AuditEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.lock().await.write_all(&line)
+    }
+
+    /// Record an entry. Failing to persist or mirror it is logged but never fails the action.
+    pub async fn record(&self, http: &Http, entry: AuditEntry) {
+        info!(
+            "audit: {} {} {}: {} {}",
+            entry.actor,
+            poise::ChoiceParameter::name(&entry.action),
+            entry.target,
+            if entry.success { "ok" } else { "failed" },
+            entry.detail
+        );
+
+        if let Some(channel_id) = self.channel_id {
+            let message = CreateMessage::new()
+                .allowed_mentions(CreateAllowedMentions::new())
+                .content(entry.line());
+            if let Err(err) = channel_id.send_message(http, message).await {
+                error!("Failed to mirror audit entry to Discord: {:?}", err);
+            }
+        }
+        if let Err(err) = self.append(&entry).await {
+            error!("Failed to persist audit entry: {:?}", err);
+        }
+        let mut recent = self.recent.lock().await;
+        if recent.len() == MAX_RECENT_ENTRIES {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+
+    /// The newest entries matching `filter` among the last `MAX_RECENT_ENTRIES`, newest first.
+    pub async fn recent(
+        &self,
+        limit: usize,
+        filter: impl Fn(&AuditEntry) -> bool + Send + Sync,
+    ) -> Vec<AuditEntry> {
+        self.recent
+            .lock()
+            .await
+            .iter()
+            .rev()
+            .filter(|entry| filter(entry))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
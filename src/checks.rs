@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use poise::{
+    serenity_prelude::{Error, GuildId, RoleId, UserId},
+    CreateReply,
+};
+
+use crate::{
+    ctfnote_api::{get_role, CtfnoteRole},
+    Context, Data,
+};
+
+const DEFAULT_ROLE_CACHE_SECONDS: u64 = 60;
+
+pub struct CachedRole {
+    role: Option<CtfnoteRole>,
+    fetched: Instant,
+}
+
+/// Look up a user's CTFNote role, reusing lookups younger than `role_cache_seconds`.
+pub async fn cached_ctfnote_role(data: &Data, user: UserId) -> Result<Option<CtfnoteRole>, Error> {
+    let ttl = Duration::from_secs(
+        data.config
+            .ctfnote
+            .role_cache_seconds
+            .unwrap_or(DEFAULT_ROLE_CACHE_SECONDS),
+    );
+    if let Some(cached) = data.role_cache.lock().await.get(&user) {
+        if cached.fetched.elapsed() < ttl {
+            return Ok(cached.role);
+        }
+    }
+
+    let role = get_role(&data.config.ctfnote, user).await?;
+    data.role_cache.lock().await.insert(
+        user,
+        CachedRole {
+            role,
+            fetched: Instant::now(),
+        },
+    );
+    Ok(role)
+}
+
+/// Drop a cached role lookup, e.g. after the user's CTFNote link changed.
+pub async fn forget_ctfnote_role(data: &Data, user: UserId) {
+    data.role_cache.lock().await.remove(&user);
+}
+
+async fn has_override_role(ctx: Context<'_>) -> Result<bool, Error> {
+    let override_role_ids = &ctx.data().config.ctfnote.override_role_ids;
+    if override_role_ids.is_empty() {
+        return Ok(false);
+    }
+
+    let member = match GuildId::new(ctx.data().config.guild_id)
+        .member(ctx, ctx.author().id)
+        .await
+    {
+        Ok(member) => member,
+        Err(Error::Http(_)) => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    Ok(override_role_ids
+        .iter()
+        .any(|&role_id| member.roles.contains(&RoleId::new(role_id))))
+}
+
+/// Allow the command if the author's CTFNote role is at least `minimum`, or if they hold one of
+/// the configured override roles. Otherwise tell them why and deny it.
+async fn require_ctfnote_role(ctx: Context<'_>, minimum: CtfnoteRole) -> Result<bool, Error> {
+    if has_override_role(ctx).await? {
+        return Ok(true);
+    }
+
+    let role = cached_ctfnote_role(ctx.data(), ctx.author().id).await?;
+    if role >= Some(minimum) {
+        return Ok(true);
+    }
+
+    let templates = &ctx.data().config.templates;
+    let reason = role.map_or_else(
+        || templates.render(&templates.not_linked, &[]),
+        |role| templates.render(&templates.current_ctfnote_role, &[("role", &role.name())]),
+    );
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(templates.render(
+                &templates.ctfnote_role_required,
+                &[("role", &minimum.name()), ("reason", &reason)],
+            )),
+    )
+    .await?;
+    Ok(false)
+}
+
+pub async fn ctfnote_manager(ctx: Context<'_>) -> Result<bool, Error> {
+    require_ctfnote_role(ctx, CtfnoteRole::Manager).await
+}
+
+pub async fn ctfnote_admin(ctx: Context<'_>) -> Result<bool, Error> {
+    require_ctfnote_role(ctx, CtfnoteRole::Admin).await
+}
@@ -0,0 +1,65 @@
+use poise::{
+    serenity_prelude::{CreateAllowedMentions, Error, User},
+    CreateReply,
+};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    checks::ctfnote_admin,
+    Context,
+};
+
+// Stay below Discord's 2000 character message limit.
+const MAX_REPLY_LEN: usize = 1900;
+
+/// Show recent privileged actions taken by the bot
+#[poise::command(slash_command, guild_only, check = "ctfnote_admin")]
+pub async fn audit(
+    ctx: Context<'_>,
+    #[description = "Only actions by this user"] actor: Option<User>,
+    #[description = "Only this kind of action"] action: Option<AuditAction>,
+    #[description = "Number of entries to show (default: 15)"]
+    #[min = 1]
+    #[max = 50]
+    limit: Option<usize>,
+) -> Result<(), Error> {
+    let actor_id = actor.map(|actor| actor.id.get());
+    let entries = ctx
+        .data()
+        .audit
+        .recent(limit.unwrap_or(15), |entry: &AuditEntry| {
+            actor_id.map_or(true, |id| entry.actor_id == Some(id))
+                && action.map_or(true, |action| entry.action == action)
+        })
+        .await;
+
+    let templates = &ctx.data().config.templates;
+    let mut lines = Vec::new();
+    let mut len = 0;
+    for entry in &entries {
+        let line = entry.line();
+        len += line.len() + 1;
+        if len > MAX_REPLY_LEN {
+            lines.push(templates.render(
+                &templates.older_audit_entries,
+                &[("count", &(entries.len() - lines.len()))],
+            ));
+            break;
+        }
+        lines.push(line);
+    }
+    let content = if lines.is_empty() {
+        templates.render(&templates.no_audit_entries, &[])
+    } else {
+        lines.join("\n")
+    };
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .content(content),
+    )
+    .await?;
+    Ok(())
+}
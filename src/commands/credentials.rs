@@ -0,0 +1,158 @@
+use std::{collections::HashSet, sync::Arc};
+
+use poise::{
+    serenity_prelude::{self as serenity, ChannelType, CreateMessage, Error, GuildId},
+    CreateReply,
+};
+use serde::Deserialize;
+use slug::slugify;
+use tracing::{error, info, warn};
+
+use crate::{
+    ctfnote_api::{get_ctf_credentials, get_ctf_participants, get_current_ctfs, Ctf},
+    storage::JsonStore,
+    templates::Templates,
+    Config, Context, CredentialsConfig,
+};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialsDelivery {
+    /// DM every linked participant.
+    Dm,
+    /// Post in the guild channel named after the CTF.
+    Channel,
+}
+
+fn credentials_message(templates: &Templates, ctf: &Ctf, credentials: &str) -> String {
+    templates.render(
+        &templates.ctf_credentials,
+        &[("ctf", &ctf.title), ("credentials", &credentials)],
+    )
+}
+
+async fn deliver_credentials(
+    ctx: &serenity::Context,
+    config: &Config,
+    delivery: CredentialsDelivery,
+    ctf: &Ctf,
+    credentials: &str,
+) -> Result<(), Error> {
+    let message =
+        CreateMessage::new().content(credentials_message(&config.templates, ctf, credentials));
+    match delivery {
+        CredentialsDelivery::Dm => {
+            for participant in get_ctf_participants(&config.ctfnote, ctf.id).await? {
+                let Some(user_id) = participant.discord_user_id() else {
+                    continue;
+                };
+                if let Err(err) = user_id.direct_message(ctx, message.clone()).await {
+                    warn!(
+                        "Failed to DM {} credentials to {}: {:?}",
+                        ctf.title, participant.username, err
+                    );
+                }
+            }
+        }
+        CredentialsDelivery::Channel => {
+            let channel_name = slugify(&ctf.title);
+            let channel = GuildId::new(config.guild_id)
+                .channels(ctx)
+                .await?
+                .into_values()
+                .find(|channel| channel.kind == ChannelType::Text && channel.name == channel_name);
+            if let Some(channel) = channel {
+                channel.send_message(ctx, message).await?;
+            } else {
+                warn!(
+                    "No #{} channel to post {} credentials in",
+                    channel_name, ctf.title
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn deliver_started_ctfs(
+    ctx: &serenity::Context,
+    config: &Config,
+    credentials_config: &CredentialsConfig,
+    delivered_ctfs: &JsonStore<HashSet<i32>>,
+) -> Result<(), Error> {
+    for ctf in get_current_ctfs(&config.ctfnote).await? {
+        if delivered_ctfs
+            .read(|delivered| delivered.contains(&ctf.id))
+            .await
+        {
+            continue;
+        }
+        // Managers may only fill the credentials in after the start, so keep checking.
+        let Some(credentials) = get_ctf_credentials(&config.ctfnote, ctf.id).await? else {
+            continue;
+        };
+
+        deliver_credentials(ctx, config, credentials_config.delivery, &ctf, &credentials).await?;
+        delivered_ctfs
+            .update(|delivered| delivered.insert(ctf.id))
+            .await?;
+        info!("delivered credentials for CTFNote CTF {}", ctf.title);
+    }
+    Ok(())
+}
+
+pub fn credentials_loop(
+    config: Config,
+    ctx: serenity::Context,
+    delivered_ctfs: Arc<JsonStore<HashSet<i32>>>,
+) {
+    let Some(credentials_config) = config.ctfnote.credentials.clone() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+            credentials_config.loop_seconds,
+        ));
+        loop {
+            interval.tick().await;
+            if let Err(err) =
+                deliver_started_ctfs(&ctx, &config, &credentials_config, &delivered_ctfs).await
+            {
+                error!("Failed to deliver CTF credentials: {:?}", err);
+            }
+        }
+    });
+}
+
+/// Get the credentials of the running CTFs you joined on CTFNote
+#[poise::command(slash_command)]
+pub async fn ctf_credentials(ctx: Context<'_>) -> Result<(), Error> {
+    let config = &ctx.data().config;
+    let templates = &config.templates;
+    let author = ctx.author().id;
+
+    let mut messages = Vec::new();
+    for ctf in get_current_ctfs(&config.ctfnote).await? {
+        let joined = get_ctf_participants(&config.ctfnote, ctf.id)
+            .await?
+            .iter()
+            .any(|participant| participant.discord_user_id() == Some(author));
+        if !joined {
+            continue;
+        }
+        let credentials = get_ctf_credentials(&config.ctfnote, ctf.id)
+            .await?
+            .unwrap_or_else(|| templates.render(&templates.no_credentials, &[]));
+        messages.push(credentials_message(templates, &ctf, &credentials));
+    }
+
+    let content = if messages.is_empty() {
+        templates.render(&templates.no_joined_ctf, &[])
+    } else {
+        messages.join("\n\n")
+    };
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
+    Ok(())
+}
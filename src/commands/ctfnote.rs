@@ -1,59 +1,153 @@
-use std::vec;
+use std::{collections::HashSet, future::Future, sync::Arc, time::Duration, vec};
 
-use chrono::{serde::ts_seconds, DateTime, Utc};
+use chrono::{serde::ts_seconds, DateTime, NaiveDateTime, TimeZone, Utc};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use poise::{
     serenity_prelude::{
-        ComponentInteractionCollector, CreateActionRow, CreateAllowedMentions, CreateButton,
-        CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, Error,
+        self as serenity, ChannelId, ComponentInteraction, ComponentInteractionCollector,
+        CreateActionRow, CreateAllowedMentions, CreateButton, CreateEmbed,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, Error,
+        ModalInteractionCollector,
     },
-    CreateReply,
+    CreateReply, Modal,
 };
 use serde::{Deserialize, Serialize};
 use slug::slugify;
+use tracing::{error, info, warn};
+
+use crate::{
+    audit::{AuditAction, AuditEntry, AuditLog},
+    checks::{ctfnote_manager, forget_ctfnote_role},
+    ctfnote_api::{
+        admin_get, admin_post, check_username, get_upcoming_ctfs, get_users, is_username_char,
+        link_discord, register, unlink_discord, Ctf, CtfnoteRole, CtfnoteUser,
+    },
+    storage::JsonStore,
+    ApplicationContext, Config, Context, CtfnoteConfig, Data, DEFAULT_JWT_AUDIENCE,
+};
 
-use crate::Context;
+const MODAL_TIMEOUT: Duration = Duration::from_secs(600);
 
-#[derive(Serialize)]
-struct CtfnoteLinkRequest {
-    token: String,
-    discord_id: String,
+pub enum Submission {
+    Accepted(String),
+    Rejected(String),
 }
 
-#[derive(Deserialize)]
-struct CtfnoteLinkResponse {
-    message: String,
+/// Open the modal `M` for a slash command and pass every submission to `submit`. Rejections are
+/// shown to the user together with a button that reopens the modal with their previous input.
+pub async fn run_modal<M, F, Fut>(
+    ctx: ApplicationContext<'_>,
+    defaults: Option<M>,
+    mut submit: F,
+) -> Result<(), Error>
+where
+    M: Modal + Clone + Send,
+    F: FnMut(M) -> Fut + Send,
+    Fut: Future<Output = Result<Submission, Error>> + Send,
+{
+    let templates = &ctx.data().config.templates;
+    let modal_id = ctx.interaction.id.to_string();
+    let retry_id = format!("{modal_id}:retry");
+    ctx.interaction
+        .create_response(ctx, M::create(defaults, modal_id.clone()))
+        .await?;
+    ctx.has_sent_initial_response
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+
+    loop {
+        let filter_id = modal_id.clone();
+        let Some(submitted) = ModalInteractionCollector::new(ctx)
+            .author_id(ctx.interaction.user.id)
+            .filter(move |submitted| submitted.data.custom_id == filter_id)
+            .timeout(MODAL_TIMEOUT)
+            .await
+        else {
+            return Ok(());
+        };
+        let modal = M::parse(submitted.data.clone()).map_err(Error::Other)?;
+
+        let problem = match submit(modal.clone()).await? {
+            Submission::Accepted(message) => {
+                submitted
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .ephemeral(true)
+                                .content(message),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            Submission::Rejected(problem) => problem,
+        };
+
+        submitted
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(problem)
+                        .components(vec![CreateActionRow::Buttons(vec![CreateButton::new(
+                            retry_id.clone(),
+                        )
+                        .label(templates.render(&templates.try_again_button, &[]))])]),
+                ),
+            )
+            .await?;
+
+        let filter_id = retry_id.clone();
+        let Some(retry) = ComponentInteractionCollector::new(ctx)
+            .author_id(ctx.interaction.user.id)
+            .filter(move |mci| mci.data.custom_id == filter_id)
+            .timeout(MODAL_TIMEOUT)
+            .await
+        else {
+            return Ok(());
+        };
+        retry
+            .create_response(ctx, M::create(Some(modal), modal_id.clone()))
+            .await?;
+    }
+}
+
+#[derive(Clone, Modal)]
+#[name = "Link your CTFNote account"]
+struct LinkModal {
+    #[name = "CTFNote account token"]
+    #[placeholder = "Found in your CTFNote profile"]
+    token: String,
 }
 
 /// Connect your Discord account to your CTFNote account!
 #[poise::command(slash_command)]
-pub async fn ctfnote_link(
-    ctx: Context<'_>,
-    #[description = "Your CTFNote account token (found in your profile)"] token: String,
-) -> Result<(), Error> {
-    let config = &ctx.data().config;
-    let ctfnote_url = &config.ctfnote.ctfnote_url;
-    let ctfnote_admin_api_password = &config.ctfnote.ctfnote_admin_api_password;
-
-    let discord_id = ctx.author().id;
-    let client = reqwest::Client::new();
-    let res = client
-        .post(format!("{}/extra/api/admin/link-discord", ctfnote_url))
-        .basic_auth("admin", Some(ctfnote_admin_api_password))
-        .json(&CtfnoteLinkRequest {
-            token: token.to_string(),
-            discord_id: discord_id.to_string(),
-        })
-        .send()
-        .await?;
-    let response = res.json::<CtfnoteLinkResponse>().await?;
-
-    ctx.send(
-        CreateReply::default()
-            .ephemeral(true)
-            .content(format!("{}", response.message)),
-    )
-    .await?;
-    Ok(())
+pub async fn ctfnote_link(ctx: ApplicationContext<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let discord_id = ctx.interaction.user.id;
+
+    run_modal(ctx, None, |modal: LinkModal| async move {
+        let outcome = link_discord(&data.config.ctfnote, modal.token.trim(), discord_id).await?;
+        data.audit
+            .record(
+                &ctx.serenity_context.http,
+                AuditEntry::new(
+                    &ctx.interaction.user,
+                    AuditAction::LinkAccount,
+                    format!("<@{discord_id}>"),
+                )
+                .outcome(outcome.success, outcome.message.clone()),
+            )
+            .await;
+        if !outcome.success {
+            return Ok(Submission::Rejected(outcome.message));
+        }
+        forget_ctfnote_role(data, discord_id).await;
+        data.token_cache.lock().await.remove(&discord_id);
+        Ok(Submission::Accepted(outcome.message))
+    })
+    .await
 }
 
 #[derive(Serialize)]
@@ -75,146 +169,355 @@ pub struct Token {
     exp: i64,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 #[allow(dead_code)]
-struct JwtClaim {
+pub struct JwtClaim {
     user_id: i32,
-    role: String,
-    exp: usize,
-    iat: usize,
+    role: CtfnoteRole,
+    exp: i64,
+    iat: i64,
     aud: String,
     iss: String,
 }
 
+#[derive(Clone)]
+pub struct CachedToken {
+    token: String,
+    claims: JwtClaim,
+}
+
+// Don't hand out cached tokens that are about to expire.
+const TOKEN_EXPIRY_MARGIN_SECONDS: i64 = 60;
+
+/// Decode a CTFNote login token, verifying its signature if a secret or public key is
+/// configured. Expired tokens and tokens for another audience are rejected either way.
+fn decode_token(config: &CtfnoteConfig, token: &str) -> jsonwebtoken::errors::Result<JwtClaim> {
+    let jwt = config.jwt.as_ref();
+    let (key, mut validation) = match (
+        jwt.and_then(|jwt| jwt.secret.as_deref()),
+        jwt.and_then(|jwt| jwt.public_key.as_deref()),
+    ) {
+        (Some(secret), _) => (
+            DecodingKey::from_secret(secret.as_bytes()),
+            Validation::new(Algorithm::HS256),
+        ),
+        (None, Some(public_key)) => (
+            DecodingKey::from_rsa_pem(public_key.as_bytes())?,
+            Validation::new(Algorithm::RS256),
+        ),
+        (None, None) => {
+            let mut validation = Validation::default();
+            validation.insecure_disable_signature_validation();
+            (DecodingKey::from_secret(&[]), validation)
+        }
+    };
+    validation.set_audience(&[jwt.map_or(DEFAULT_JWT_AUDIENCE, |jwt| jwt.audience.as_str())]);
+    Ok(decode::<JwtClaim>(token, &key, &validation)?.claims)
+}
+
 /// Login to CTFNote account.
 #[poise::command(slash_command)]
 pub async fn ctfnote_login(ctx: Context<'_>) -> Result<(), Error> {
-    let config = &ctx.data().config;
-    let ctfnote_url = &config.ctfnote.ctfnote_url;
-    let ctfnote_admin_api_password = &config.ctfnote.ctfnote_admin_api_password;
-
+    let data = ctx.data();
+    let config = &data.config.ctfnote;
     let discord_id = ctx.author().id;
-    let client = reqwest::Client::new();
-    let token_endpoint = format!("{}/extra/api/admin/get-token", ctfnote_url);
-    let res = client
-        .post(&token_endpoint)
-        .basic_auth("admin", Some(ctfnote_admin_api_password))
-        .json(&GetTokenForDiscordUserRequest {
-            discord_id: discord_id.to_string(),
-        })
-        .send()
+
+    // Don't hold the cache lock across the CTFNote request, other commands use it too.
+    let cached = data
+        .token_cache
+        .lock()
+        .await
+        .get(&discord_id)
+        .filter(|cached| cached.claims.exp > Utc::now().timestamp() + TOKEN_EXPIRY_MARGIN_SECONDS)
+        .cloned();
+    let cached = if let Some(cached) = cached {
+        cached
+    } else {
+        let response: GetTokenForDiscordUserResponse = admin_post(
+            config,
+            "get-token",
+            &GetTokenForDiscordUserRequest {
+                discord_id: discord_id.to_string(),
+            },
+        )
         .await?;
-    let response = res.json::<GetTokenForDiscordUserResponse>().await?;
-    let token = response.token;
-    match token {
-        Some(token) => {
-            ctx.send(CreateReply::default().ephemeral(true).content(format!(
-                "<{}/extra/token-login?token={}>\nExpires <t:{}>",
-                ctfnote_url, token.token, token.exp
-            )))
-            .await?;
-        }
-        None => {
+        let Some(token) = response.token else {
             ctx.send(
                 CreateReply::default()
                     .ephemeral(true)
-                    .content(format!("{}", response.message)),
+                    .content(response.message),
             )
             .await?;
+            return Ok(());
+        };
+
+        match decode_token(config, &token.token) {
+            Ok(claims) => {
+                let cached = CachedToken {
+                    token: token.token,
+                    claims,
+                };
+                data.token_cache
+                    .lock()
+                    .await
+                    .insert(discord_id, cached.clone());
+                cached
+            }
+            Err(err) => {
+                warn!("refused CTFNote token for {}: {:?}", ctx.author().name, err);
+                let templates = &data.config.templates;
+                ctx.send(
+                    CreateReply::default()
+                        .ephemeral(true)
+                        .content(templates.render(&templates.invalid_login_token, &[])),
+                )
+                .await?;
+                return Ok(());
+            }
         }
-    }
+    };
 
+    let templates = &data.config.templates;
+    let content = templates.render(
+        &templates.login_link,
+        &[
+            (
+                "url",
+                &format!(
+                    "{}/extra/token-login?token={}",
+                    config.ctfnote_url, cached.token
+                ),
+            ),
+            ("role", &cached.claims.role.name()),
+            ("time", &format!("<t:{}>", cached.claims.exp)),
+        ],
+    );
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
     Ok(())
 }
 
 #[derive(Serialize)]
-struct CtfnoteRegisterRequest {
-    username: String,
+struct DiscordUserRequest {
     discord_id: String,
 }
 
 #[derive(Deserialize)]
-struct CtfnoteRegisterResponse {
+struct WhoamiResponse {
+    user: Option<CtfnoteUser>,
     message: String,
 }
 
-/// Create CTFNote account
-#[poise::command(slash_command, guild_only)]
-pub async fn ctfnote_create_account(
-    ctx: Context<'_>,
-    #[description = "Username"] username: Option<String>,
-) -> Result<(), Error> {
-    let config = &ctx.data().config;
-
-    // only runs in team channel
-    let team_channel_id = config.team_channel_id;
-    let channel_id = ctx.channel_id().get();
-    if channel_id != team_channel_id {
-        ctx.reply(format!(
-            "You can only run this command in team channel <#{}>",
-            team_channel_id
-        ))
-        .await?;
-        return Ok(());
-    }
+/// Show the CTFNote account linked to your Discord account
+#[poise::command(slash_command)]
+pub async fn ctfnote_whoami(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let config = &data.config;
+    let request = DiscordUserRequest {
+        discord_id: ctx.author().id.to_string(),
+    };
 
-    let client = reqwest::Client::new();
-    let author = ctx.author();
-    let username = match username {
-        Some(username) => username,
-        None => author.name.clone(),
+    let response: WhoamiResponse = admin_get(&config.ctfnote, "whoami", &request).await?;
+    let content = match response.user {
+        Some(user) => {
+            // Only report the token /ctfnote_login decoded and handed out, asking CTFNote for
+            // one would mint a new token.
+            let exp = data
+                .token_cache
+                .lock()
+                .await
+                .get(&ctx.author().id)
+                .map(|cached| cached.claims.exp)
+                .filter(|&exp| exp > Utc::now().timestamp());
+            let templates = &config.templates;
+            let expiry = exp.map_or_else(
+                || templates.render(&templates.no_login_token, &[]),
+                |exp| {
+                    templates.render(
+                        &templates.login_token_expiry,
+                        &[("time", &format!("<t:{exp}:R>"))],
+                    )
+                },
+            );
+            templates.render(
+                &templates.linked_account,
+                &[
+                    ("username", &user.username),
+                    ("role", &user.role.name()),
+                    ("expiry", &expiry),
+                ],
+            )
+        }
+        None => response.message,
     };
-    let discord_id = author.id;
-    let res = client
-        .post(format!(
-            "{}/extra/api/admin/register",
-            &config.ctfnote.ctfnote_url
-        ))
-        .basic_auth("admin", Some(&config.ctfnote.ctfnote_admin_api_password))
-        .json(&CtfnoteRegisterRequest {
-            username,
-            discord_id: discord_id.to_string(),
-        })
-        .send()
-        .await?;
-    let response = res.json::<CtfnoteRegisterResponse>().await?;
-    ctx.reply(format!("{}", response.message)).await?;
 
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
     Ok(())
 }
 
-#[derive(Serialize)]
-#[allow(dead_code)]
-struct GetRoleForDiscordUserRequest {
-    discord_id: String,
+/// Disconnect your Discord account from your CTFNote account
+#[poise::command(slash_command)]
+pub async fn ctfnote_unlink(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let discord_id = ctx.author().id;
+
+    let outcome = unlink_discord(&data.config.ctfnote, discord_id).await?;
+    if outcome.success {
+        forget_ctfnote_role(data, discord_id).await;
+        data.token_cache.lock().await.remove(&discord_id);
+    }
+    data.audit
+        .record(
+            ctx.http(),
+            AuditEntry::new(
+                ctx.author(),
+                AuditAction::UnlinkAccount,
+                format!("<@{discord_id}>"),
+            )
+            .outcome(outcome.success, outcome.message.clone()),
+        )
+        .await;
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(outcome.message),
+    )
+    .await?;
+    Ok(())
 }
 
 #[derive(Deserialize)]
-#[allow(dead_code)]
-struct GetRoleForDiscordUserResponse {
-    role: Option<String>,
+struct CtfnoteResetPasswordResponse {
+    token: Option<String>,
     message: String,
 }
 
-#[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-struct Ctf {
-    id: i32,
-    title: String,
-    weight: f64,
-    ctf_url: Option<String>,
-    logo_url: Option<String>,
-    ctftime_url: Option<String>,
-    description: String,
-    #[serde(with = "ts_seconds")]
-    start_time: DateTime<Utc>,
-    #[serde(with = "ts_seconds")]
-    end_time: DateTime<Utc>,
-    // secrets_id: foreign key
+/// Get a one-time link to reset your CTFNote password by DM
+#[poise::command(slash_command)]
+pub async fn ctfnote_reset_password(ctx: Context<'_>) -> Result<(), Error> {
+    let config = &ctx.data().config;
+
+    let response: CtfnoteResetPasswordResponse = admin_post(
+        &config.ctfnote,
+        "reset-password",
+        &DiscordUserRequest {
+            discord_id: ctx.author().id.to_string(),
+        },
+    )
+    .await?;
+    ctx.data()
+        .audit
+        .record(
+            ctx.http(),
+            AuditEntry::new(
+                ctx.author(),
+                AuditAction::ResetPassword,
+                format!("<@{}>", ctx.author().id),
+            )
+            .outcome(response.token.is_some(), response.message.clone()),
+        )
+        .await;
+    let Some(token) = response.token else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(response.message),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let templates = &config.templates;
+    let dm = ctx
+        .author()
+        .direct_message(
+            ctx,
+            CreateMessage::new().content(templates.render(
+                &templates.password_reset_link,
+                &[(
+                    "url",
+                    &format!(
+                        "{}/#/auth/reset-password/{}",
+                        config.ctfnote.ctfnote_url, token
+                    ),
+                )],
+            )),
+        )
+        .await;
+    let content = if dm.is_ok() {
+        templates.render(&templates.password_reset_sent, &[])
+    } else {
+        templates.render(&templates.dm_failed, &[])
+    };
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
+    Ok(())
 }
 
-#[derive(Deserialize)]
-struct GetUpcomingCtfResponse(Vec<Ctf>);
+#[derive(Clone, Modal)]
+#[name = "Create your CTFNote account"]
+struct CreateAccountModal {
+    #[name = "Username"]
+    #[min_length = 3]
+    #[max_length = 32]
+    username: String,
+}
+
+/// Create CTFNote account
+#[poise::command(slash_command, guild_only)]
+pub async fn ctfnote_create_account(ctx: ApplicationContext<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+
+    // only runs in team channel
+    let team_channel_id = data.config.team_channel_id;
+    if ctx.interaction.channel_id.get() != team_channel_id {
+        let templates = &data.config.templates;
+        poise::Context::Application(ctx)
+            .reply(templates.render(
+                &templates.team_channel_only,
+                &[("channel", &format!("<#{team_channel_id}>"))],
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    let author = &ctx.interaction.user;
+    let defaults = CreateAccountModal {
+        username: author
+            .name
+            .chars()
+            .filter(|&c| is_username_char(c))
+            .collect(),
+    };
+    run_modal(
+        ctx,
+        Some(defaults),
+        |modal: CreateAccountModal| async move {
+            let username = modal.username.trim();
+            let users = get_users(&data.config.ctfnote).await?;
+            if let Err(problem) = check_username(&data.config.templates, username, &users) {
+                return Ok(Submission::Rejected(problem));
+            }
+
+            let outcome = register(&data.config.ctfnote, username, author.id).await?;
+            data.audit
+                .record(
+                    &ctx.serenity_context.http,
+                    AuditEntry::new(author, AuditAction::CreateAccount, username)
+                        .outcome(outcome.success, outcome.message.clone()),
+                )
+                .await;
+            Ok(if outcome.success {
+                Submission::Accepted(outcome.message)
+            } else {
+                Submission::Rejected(outcome.message)
+            })
+        },
+    )
+    .await
+}
 
 #[derive(Serialize)]
 struct AddDiscordUserToCtfRequest {
@@ -227,125 +530,313 @@ struct AddDiscordUserToCtfResponse {
     message: String,
 }
 
+const JOIN_CTF_PREFIX: &str = "ctfnote_join_ctf:";
+
+/// Build the announcement embed for a CTFNote CTF, with buttons to join and view it.
+pub fn ctf_announcement(ctf: &Ctf, config: &Config) -> (CreateEmbed, CreateActionRow) {
+    let templates = &config.templates;
+    let custom_id = format!("{}{}", JOIN_CTF_PREFIX, ctf.id);
+    let ctfnote_link = format!(
+        "{}/#/ctf/{}-{}",
+        config.ctfnote.ctfnote_url,
+        ctf.id,
+        slugify(&ctf.title)
+    );
+    let mut embed = CreateEmbed::new()
+        .title(templates.render(&templates.embed_title, &[("ctf", &ctf.title)]))
+        .description(&ctf.description)
+        .field(
+            templates.render(&templates.embed_dates, &[]),
+            templates.render(
+                &templates.embed_dates_value,
+                &[
+                    ("start", &format!("<t:{}:f>", ctf.start_time.timestamp())),
+                    ("end", &format!("<t:{}:f>", ctf.end_time.timestamp())),
+                ],
+            ),
+            true,
+        )
+        .fields([(
+            templates.render(&templates.embed_weight, &[]),
+            ctf.weight.to_string(),
+            true,
+        )]);
+    if let Some(ctftime_url) = &ctf.ctftime_url {
+        embed = embed.url(ctftime_url);
+    }
+    if let Some(logo_url) = &ctf.logo_url {
+        embed = embed.thumbnail(logo_url);
+    }
+    if let Some(ctf_url) = &ctf.ctf_url {
+        embed = embed.field(
+            templates.render(&templates.embed_ctf_page, &[]),
+            ctf_url,
+            true,
+        );
+    }
+
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(custom_id).label(templates.render(&templates.join_button, &[])),
+        CreateButton::new_link(ctfnote_link).label(templates.render(&templates.view_button, &[])),
+    ]);
+    (embed, buttons)
+}
+
+/// Handle a click on the "Join on CTFNote" button of any CTF announcement.
+pub async fn handle_join_button(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+) -> Result<(), Error> {
+    let Some(ctf_id) = mci
+        .data
+        .custom_id
+        .strip_prefix(JOIN_CTF_PREFIX)
+        .and_then(|id| id.parse().ok())
+    else {
+        return Ok(());
+    };
+
+    let response: AddDiscordUserToCtfResponse = admin_post(
+        &data.config.ctfnote,
+        "add-to-ctf",
+        &AddDiscordUserToCtfRequest {
+            discord_id: mci.user.id.to_string(),
+            ctf_id,
+        },
+    )
+    .await?;
+    mci.create_response(
+        ctx,
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::default()
+                .allowed_mentions(CreateAllowedMentions::new().users(vec![mci.user.id]))
+                .content(format!("<@{}> {}", mci.user.id, response.message)),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
 /// Announce upcoming CTFs on CTFNote in the channel
-#[poise::command(slash_command, guild_only)]
+#[poise::command(slash_command, guild_only, check = "ctfnote_manager")]
 pub async fn ctfnote_announce_upcoming(ctx: Context<'_>) -> Result<(), Error> {
-    let config = &ctx.data().config;
+    let data = ctx.data();
+    let ctfs = get_upcoming_ctfs(&data.config.ctfnote).await?;
 
-    // only CTFNote manager or admin can announce
-    let discord_id = ctx.author().id;
-    let client = reqwest::Client::new();
-    let res = client
-        .get(format!(
-            "{}/extra/api/admin/role",
-            &config.ctfnote.ctfnote_url
-        ))
-        .basic_auth("admin", Some(&config.ctfnote.ctfnote_admin_api_password))
-        .query(&GetRoleForDiscordUserRequest {
-            discord_id: discord_id.to_string(),
-        })
-        .send()
-        .await?;
-    let response = res.json::<GetRoleForDiscordUserResponse>().await?;
-    let role = response.role;
-    let role_string = role.unwrap_or("".to_string());
-    if role_string != "user_manager" && role_string != "user_admin" {
-        ctx.reply(
-            "You need to be CTFNote manager or admin, or your Discord is not linked to CTFNote",
-        )
-        .await?;
+    if ctfs.is_empty() {
+        let templates = &data.config.templates;
+        ctx.reply(templates.render(&templates.no_upcoming_ctfnote, &[]))
+            .await?;
         return Ok(());
     }
 
-    let res = client
-        .get(format!(
-            "{}/extra/api/admin/upcoming-ctf",
-            &config.ctfnote.ctfnote_url
-        ))
-        .basic_auth("admin", Some(&config.ctfnote.ctfnote_admin_api_password))
-        .send()
+    for ctf in ctfs {
+        let (embed, buttons) = ctf_announcement(&ctf, &data.config);
+        ctx.send(
+            CreateReply::default()
+                .embed(embed)
+                .components(vec![buttons]),
+        )
         .await?;
-    let response = res.json::<GetUpcomingCtfResponse>().await?;
+        // Don't let the announcement loop post it a second time.
+        data.announced_ctfs
+            .update(|announced| announced.insert(ctf.id))
+            .await?;
+        data.audit
+            .record(
+                ctx.http(),
+                AuditEntry::new(ctx.author(), AuditAction::AnnounceCtf, ctf.title),
+            )
+            .await;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct CreateCtfRequest {
+    title: String,
+    #[serde(with = "ts_seconds")]
+    start_time: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    end_time: DateTime<Utc>,
+    weight: f64,
+    ctf_url: Option<String>,
+    description: String,
+    logo_url: Option<String>,
+}
 
-    if response.0.len() == 0 {
-        ctx.reply("No upcoming CTFs on CTFNote.").await?;
+#[derive(Deserialize)]
+struct CreateCtfResponse {
+    ctf: Option<Ctf>,
+    message: String,
+}
+
+/// Parse a `YYYY-MM-DD HH:MM` time in UTC, or an RFC 3339 time with an explicit offset.
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(time.trim(), "%Y-%m-%d %H:%M")
+        .map(|time| Utc.from_utc_datetime(&time))
+        .or_else(|_| DateTime::parse_from_rfc3339(time.trim()).map(|time| time.with_timezone(&Utc)))
+        .ok()
+}
+
+/// Create a CTF on CTFNote, e.g. a private or university CTF that isn't on CTFtime
+#[poise::command(slash_command, guild_only, check = "ctfnote_manager")]
+#[allow(clippy::too_many_arguments)]
+pub async fn ctfnote_create_ctf(
+    ctx: Context<'_>,
+    #[description = "Title of the CTF"] title: String,
+    #[description = "Start time in UTC, as YYYY-MM-DD HH:MM"] start: String,
+    #[description = "End time in UTC, as YYYY-MM-DD HH:MM"] end: String,
+    #[description = "Weight of the CTF (default: 0)"] weight: Option<f64>,
+    #[description = "Link to the CTF platform"] ctf_url: Option<String>,
+    #[description = "Description of the CTF"] description: Option<String>,
+    #[description = "Link to the logo of the CTF"] logo_url: Option<String>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let templates = &data.config.templates;
+    let (Some(start_time), Some(end_time)) = (parse_time(&start), parse_time(&end)) else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(templates.render(&templates.invalid_ctf_times, &[])),
+        )
+        .await?;
+        return Ok(());
+    };
+    if end_time <= start_time {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(templates.render(&templates.ctf_ends_before_start, &[])),
+        )
+        .await?;
         return Ok(());
     }
 
-    for ctf in response.0 {
-        let custom_id = format!("ctfnote_join_ctf:{}", ctf.id);
-        let ctfnote_link = format!(
-            "{}/#/ctf/{}-{}",
-            config.ctfnote.ctfnote_url,
-            ctf.id,
-            slugify(&ctf.title)
-        );
-        let mut embed = CreateEmbed::new()
-            .title(&ctf.title)
-            .description(&ctf.description)
-            .field(
-                "Dates",
-                format!(
-                    "Starts: <t:{}:f>.\n Ends: <t:{}:f>",
-                    ctf.start_time.timestamp(),
-                    ctf.end_time.timestamp(),
-                ),
-                true,
-            )
-            .fields([("Weight", &ctf.weight.to_string(), true)]);
-        if ctf.ctftime_url.is_some() {
-            embed = embed.url(ctf.ctftime_url.unwrap());
-        }
-        if ctf.logo_url.is_some() {
-            embed = embed.thumbnail(&ctf.logo_url.unwrap());
-        }
-        if ctf.ctf_url.is_some() {
-            embed = embed.field("CTF Page", ctf.ctf_url.unwrap(), true);
-        }
+    let response: CreateCtfResponse = admin_post(
+        &data.config.ctfnote,
+        "create-ctf",
+        &CreateCtfRequest {
+            title: title.clone(),
+            start_time,
+            end_time,
+            weight: weight.unwrap_or_default(),
+            ctf_url,
+            description: description.unwrap_or_default(),
+            logo_url,
+        },
+    )
+    .await?;
+    data.audit
+        .record(
+            ctx.http(),
+            AuditEntry::new(ctx.author(), AuditAction::CreateCtf, title.as_str())
+                .outcome(response.ctf.is_some(), response.message.clone()),
+        )
+        .await;
+    let Some(ctf) = response.ctf else {
         ctx.send(
             CreateReply::default()
-                .embed(embed)
-                .components(vec![CreateActionRow::Buttons(vec![
-                    CreateButton::new(custom_id.clone()).label("Join on CTFNote"),
-                    CreateButton::new_link(ctfnote_link).label("View on CTFNote"),
-                ])]),
+                .ephemeral(true)
+                .content(response.message),
         )
         .await?;
+        return Ok(());
+    };
+
+    let (embed, buttons) = ctf_announcement(&ctf, &data.config);
+    ctx.send(
+        CreateReply::default()
+            .embed(embed)
+            .components(vec![buttons]),
+    )
+    .await?;
+    data.announced_ctfs
+        .update(|announced| announced.insert(ctf.id))
+        .await?;
+    Ok(())
+}
 
-        let mut custom_id_ = custom_id.clone();
-        while let Some(mci) = ComponentInteractionCollector::new(ctx)
-            .guild_id(ctx.guild_id().unwrap())
-            .channel_id(ctx.channel_id())
-            .timeout(std::time::Duration::from_secs(30 * 24 * 60 * 60)) // 30 days
-            .filter(move |mci| mci.data.custom_id == custom_id_.clone())
+async fn seed_announced_ctfs(
+    config: &Config,
+    announced_ctfs: &JsonStore<HashSet<i32>>,
+) -> Result<(), Error> {
+    let ctfs = get_upcoming_ctfs(&config.ctfnote).await?;
+    info!("marking {} existing CTFNote CTFs as announced", ctfs.len());
+    announced_ctfs
+        .update(|announced| announced.extend(ctfs.iter().map(|ctf| ctf.id)))
+        .await?;
+    Ok(())
+}
+
+async fn announce_new_ctfs(
+    ctx: &serenity::Context,
+    config: &Config,
+    channel_id: ChannelId,
+    announced_ctfs: &JsonStore<HashSet<i32>>,
+    audit: &AuditLog,
+) -> Result<(), Error> {
+    for ctf in get_upcoming_ctfs(&config.ctfnote).await? {
+        if announced_ctfs
+            .read(|announced| announced.contains(&ctf.id))
             .await
         {
-            let res = client
-                .post(format!(
-                    "{}/extra/api/admin/add-to-ctf",
-                    &config.ctfnote.ctfnote_url
-                ))
-                .basic_auth("admin", Some(&config.ctfnote.ctfnote_admin_api_password))
-                .json(&AddDiscordUserToCtfRequest {
-                    discord_id: mci.user.id.to_string(),
-                    ctf_id: ctf.id,
-                })
-                .send()
-                .await?;
-            let response = res.json::<AddDiscordUserToCtfResponse>().await?;
-            mci.create_response(
-                ctx,
-                CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::default()
-                        .allowed_mentions(CreateAllowedMentions::new().users(vec![mci.user.id]))
-                        .content(format!("<@{}> {}", mci.user.id, response.message)),
-                ),
+            continue;
+        }
+
+        let (embed, buttons) = ctf_announcement(&ctf, config);
+        channel_id
+            .send_message(
+                &ctx.http,
+                CreateMessage::new().embed(embed).components(vec![buttons]),
             )
             .await?;
-
-            custom_id_ = custom_id.clone();
-        }
+        announced_ctfs
+            .update(|announced| announced.insert(ctf.id))
+            .await?;
+        audit
+            .record(
+                &ctx.http,
+                AuditEntry::automatic(AuditAction::AnnounceCtf, ctf.title),
+            )
+            .await;
     }
-
     Ok(())
 }
+
+pub fn ctfnote_announce_loop(
+    config: Config,
+    ctx: serenity::Context,
+    announced_ctfs: Arc<JsonStore<HashSet<i32>>>,
+    audit: Arc<AuditLog>,
+) {
+    let Some(announce) = config.ctfnote.announce.clone() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let channel_id = ChannelId::new(announce.channel_id);
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(announce.loop_seconds));
+        // Without a dedup store, treat the CTFs that already exist as announced so a first
+        // deployment or a wiped data_dir doesn't repost all of them.
+        let mut seeded = !announced_ctfs.created();
+        loop {
+            interval.tick().await;
+            if !seeded {
+                match seed_announced_ctfs(&config, &announced_ctfs).await {
+                    Ok(()) => seeded = true,
+                    Err(err) => error!("Failed to seed the announced CTFNote CTFs: {:?}", err),
+                }
+                continue;
+            }
+            if let Err(err) =
+                announce_new_ctfs(&ctx, &config, channel_id, &announced_ctfs, &audit).await
+            {
+                error!("Failed to announce new CTFNote CTFs: {:?}", err);
+            }
+        }
+    });
+}
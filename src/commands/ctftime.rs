@@ -9,7 +9,11 @@ use poise::{serenity_prelude::{CreateEmbed, Error, GuildId, RoleId}, CreateReply
 use serde::Deserialize;
 use tracing::info;
 
-use crate::{CTFLog, Context};
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    templates::Templates,
+    CTFLog, Context,
+};
 
 #[derive(poise::ChoiceParameter)]
 pub enum TimeFrame {
@@ -98,15 +102,16 @@ pub async fn get_upcoming_ctf(
     ctx: Context<'_>,
     #[description = "Requested time frame"] timeframe: TimeFrame,
 ) -> Result<(), Error> {
+    let templates = &ctx.data().config.templates;
     let ctfs = Ctf::get_ctfs(timeframe.to_duration()).await?;
     info!("logged {:?}", &ctfs);
     if ctfs.is_empty() {
-        ctx.say("No Upcoming CTFs in that time period").await?;
+        ctx.say(templates.render(&templates.no_upcoming_ctftime, &[])).await?;
         return Ok(());
     }
 
     for ctf in &ctfs {
-        ctx.send(CreateReply::default().embed(generate_embed(ctf))).await?;
+        ctx.send(CreateReply::default().embed(generate_embed(ctf, templates))).await?;
     }
     Ok(())
 }
@@ -121,28 +126,36 @@ fn now() -> Duration {
         .unwrap()
 }
 
-pub fn generate_embed<'a>(ctf: &Ctf) -> CreateEmbed {
-    CreateEmbed::new().title(&ctf.title)
+pub fn generate_embed<'a>(ctf: &Ctf, templates: &Templates) -> CreateEmbed {
+    CreateEmbed::new().title(templates.render(&templates.embed_title, &[("ctf", &ctf.title)]))
         .description(&ctf.description)
         .thumbnail(&ctf.logo)
         .field(
-            "Dates",
-            format!(
-                "Starts: <t:{}:f>.\n Ends: <t:{}:f>",
-                ctf.finish
-                    .sub(chrono::Duration::hours(ctf.duration.hours as i64))
-                    .sub(chrono::Duration::days(ctf.duration.days as i64))
-                    .timestamp(),
-                ctf.finish.timestamp()
+            templates.render(&templates.embed_dates, &[]),
+            templates.render(
+                &templates.embed_dates_value,
+                &[
+                    (
+                        "start",
+                        &format!(
+                            "<t:{}:f>",
+                            ctf.finish
+                                .sub(chrono::Duration::hours(ctf.duration.hours as i64))
+                                .sub(chrono::Duration::days(ctf.duration.days as i64))
+                                .timestamp()
+                        ),
+                    ),
+                    ("end", &format!("<t:{}:f>", ctf.finish.timestamp())),
+                ],
             ),
             true,
         )
-        .field("CTF Page", &ctf.url, true)
+        .field(templates.render(&templates.embed_ctf_page, &[]), &ctf.url, true)
         .url(&ctf.ctftime_url)
         .fields([
-            ("Weight", &ctf.weight.to_string(), true),
-            ("Participants", &ctf.participants.to_string(), true),
-            ("Format", &ctf.format.to_string(), true),
+            (templates.render(&templates.embed_weight, &[]), &ctf.weight.to_string(), true),
+            (templates.render(&templates.embed_participants, &[]), &ctf.participants.to_string(), true),
+            (templates.render(&templates.embed_format, &[]), &ctf.format.to_string(), true),
         ])
 }
 
@@ -158,15 +171,23 @@ pub async fn assign_ctf_announcement_role(ctx: Context<'_>) -> Result<(), Error>
     let cache_http = ctx.http();
     let has_role = author.has_role(cache_http, guild, RoleId::new(config.notification_role_id)).await?;
 
-    if has_role {
-        info!("{} just removed the announcement role", author.name);
+    let action = if has_role {
         guild.member(cache_http, author.id).await.unwrap().remove_role(cache_http, role).await?;
+        AuditAction::RemoveRole
     } else {
-        info!("{} just gave themselves the announcement role", author.name);
         guild.member(cache_http, author.id).await.unwrap().add_role(cache_http, role).await?;
-    }
+        AuditAction::GrantRole
+    };
+    ctx.data()
+        .audit
+        .record(
+            cache_http,
+            AuditEntry::new(author, action, format!("CTF announcement role to <@{}>", author.id)),
+        )
+        .await;
 
-    ctx.say("Success").await?;
+    let templates = &config.templates;
+    ctx.say(templates.render(&templates.announcement_role_toggled, &[])).await?;
 
     Ok(())
 }
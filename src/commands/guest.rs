@@ -0,0 +1,210 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use poise::{
+    serenity_prelude::{
+        self as serenity, AutocompleteChoice, CreateAllowedMentions, CreateMessage, Error, User,
+    },
+    CreateReply,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    audit::{AuditAction, AuditEntry, AuditLog},
+    checks::{ctfnote_manager, forget_ctfnote_role},
+    commands::provision::unique_username,
+    ctfnote_api::{delete_user, get_current_ctfs, get_upcoming_ctfs, get_users, register_guest},
+    storage::JsonStore,
+    Config, Context,
+};
+
+const GUEST_EXPIRY_CHECK: Duration = Duration::from_secs(300);
+
+/// A guest account waiting to be deleted.
+#[derive(Serialize, Deserialize)]
+pub struct GuestAccount {
+    username: String,
+    discord_id: u64,
+    ctf_id: i32,
+    #[serde(with = "ts_seconds")]
+    expires_at: DateTime<Utc>,
+}
+
+async fn autocomplete_open_ctf(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let config = &ctx.data().config.ctfnote;
+    let partial = partial.to_lowercase();
+    let mut ctfs = get_current_ctfs(config).await.unwrap_or_default();
+    ctfs.extend(get_upcoming_ctfs(config).await.unwrap_or_default());
+    ctfs.into_iter()
+        .filter(|ctf| ctf.title.to_lowercase().contains(&partial))
+        .take(25) // Discord shows at most 25 choices
+        .map(|ctf| AutocompleteChoice::new(ctf.title, ctf.id))
+        .collect()
+}
+
+/// Give someone from outside the team a temporary CTFNote guest account for one CTF
+#[poise::command(slash_command, guild_only, check = "ctfnote_manager")]
+pub async fn ctfnote_invite_guest(
+    ctx: Context<'_>,
+    #[description = "The guest's Discord account"] user: User,
+    #[description = "The only CTF the guest can see"]
+    #[autocomplete = "autocomplete_open_ctf"]
+    ctf: i32,
+    #[description = "Days until the account is deleted"]
+    #[min = 1]
+    #[max = 90]
+    days: u32,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let config = &data.config.ctfnote;
+    let templates = &data.config.templates;
+    ctx.defer_ephemeral().await?;
+
+    let users = get_users(config).await?;
+    if let Some(existing) = users
+        .iter()
+        .find(|existing| existing.discord_user_id() == Some(user.id))
+    {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .allowed_mentions(CreateAllowedMentions::new())
+                .content(templates.render(
+                    &templates.guest_already_linked,
+                    &[("user", &user.id), ("username", &existing.username)],
+                )),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let taken: HashSet<String> = users
+        .iter()
+        .map(|existing| existing.username.to_lowercase())
+        .collect();
+    let username = unique_username(&user.name, &taken);
+    let outcome = register_guest(config, &username, user.id, ctf).await?;
+    data.audit
+        .record(
+            ctx.http(),
+            AuditEntry::new(
+                ctx.author(),
+                AuditAction::InviteGuest,
+                format!("{username} (<@{}>) for CTF {ctf}, {days} days", user.id),
+            )
+            .outcome(outcome.success, outcome.message.clone()),
+        )
+        .await;
+    if !outcome.success {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(outcome.message),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let expires_at = Utc::now() + chrono::Duration::days(i64::from(days));
+    data.guest_accounts
+        .update(|guests| {
+            guests.push(GuestAccount {
+                username: username.clone(),
+                discord_id: user.id.get(),
+                ctf_id: ctf,
+                expires_at,
+            });
+        })
+        .await?;
+    forget_ctfnote_role(data, user.id).await;
+
+    let dm = user
+        .direct_message(
+            ctx,
+            CreateMessage::new().content(templates.render(
+                &templates.guest_account_dm,
+                &[
+                    ("username", &username),
+                    ("url", &config.ctfnote_url),
+                    ("expiry", &format!("<t:{}:R>", expires_at.timestamp())),
+                    ("details", &outcome.message),
+                ],
+            )),
+        )
+        .await;
+    let delivery = if dm.is_ok() {
+        templates.render(&templates.guest_dm_sent, &[])
+    } else {
+        templates.render(&templates.guest_dm_failed, &[])
+    };
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .content(templates.render(
+                &templates.guest_created,
+                &[
+                    ("username", &username),
+                    ("user", &user.id),
+                    ("expiry", &format!("<t:{}:f>", expires_at.timestamp())),
+                    ("delivery", &delivery),
+                ],
+            )),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn delete_expired_guests(
+    ctx: &serenity::Context,
+    config: &Config,
+    guests: &JsonStore<Vec<GuestAccount>>,
+    audit: &AuditLog,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let expired: Vec<String> = guests
+        .read(|guests| {
+            guests
+                .iter()
+                .filter(|guest| guest.expires_at <= now)
+                .map(|guest| guest.username.clone())
+                .collect()
+        })
+        .await;
+
+    for username in expired {
+        let outcome = delete_user(&config.ctfnote, &username).await?;
+        audit
+            .record(
+                &ctx.http,
+                AuditEntry::automatic(AuditAction::DeleteGuest, username.as_str())
+                    .outcome(outcome.success, outcome.message),
+            )
+            .await;
+        // Failed deletions keep their entry so they are retried on the next check.
+        if outcome.success {
+            guests
+                .update(|guests| guests.retain(|guest| guest.username != username))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+pub fn guest_expiry_loop(
+    config: Config,
+    ctx: serenity::Context,
+    guests: Arc<JsonStore<Vec<GuestAccount>>>,
+    audit: Arc<AuditLog>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(GUEST_EXPIRY_CHECK);
+        loop {
+            interval.tick().await;
+            if let Err(err) = delete_expired_guests(&ctx, &config, &guests, &audit).await {
+                error!("Failed to delete expired CTFNote guests: {:?}", err);
+            }
+        }
+    });
+}
@@ -1,5 +1,12 @@
+pub mod audit;
+pub mod credentials;
 pub mod ctftime;
+pub mod guest;
+pub mod provision;
 #[allow(clippy::module_name_repetitions)]
 pub mod register_commands;
+pub mod role_sync;
 pub mod welcome;
 pub mod ctfnote;
+pub mod task;
+pub mod writeups;
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use poise::{
+    serenity_prelude::{CreateAllowedMentions, Error, GuildId, Http, Member, Role, RoleId},
+    CreateReply,
+};
+use tracing::{error, info};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    checks::ctfnote_admin,
+    ctfnote_api::{
+        get_users, is_username_char, register, AdminOutcome, CtfnoteUser, USERNAME_MAX_LEN,
+        USERNAME_MIN_LEN,
+    },
+    templates::Templates,
+    Context,
+};
+
+// Discord returns at most this many members per request.
+const MEMBERS_PAGE_SIZE: u64 = 1000;
+// Keep each list in the summary below Discord's 2000 character message limit.
+const MAX_LISTED: usize = 20;
+
+/// Every member of `guild` with the role `role_id`, except bots.
+pub async fn members_with_role(
+    http: &Http,
+    guild: GuildId,
+    role_id: RoleId,
+) -> Result<Vec<Member>, Error> {
+    let mut members = Vec::new();
+    let mut after = None;
+    loop {
+        let page = guild.members(http, Some(MEMBERS_PAGE_SIZE), after).await?;
+        after = page.last().map(|member| member.user.id);
+        let done = (page.len() as u64) < MEMBERS_PAGE_SIZE;
+        members.extend(
+            page.into_iter()
+                .filter(|member| !member.user.bot && member.roles.contains(&role_id)),
+        );
+        if done {
+            return Ok(members);
+        }
+    }
+}
+
+/// Derive a CTFNote username from a Discord username that isn't in `taken` yet.
+pub fn unique_username(discord_name: &str, taken: &HashSet<String>) -> String {
+    let mut base: String = discord_name
+        .chars()
+        .filter(|&c| is_username_char(c))
+        .take(USERNAME_MAX_LEN - 4) // leave room for a de-duplicating suffix
+        .collect();
+    if base.len() < USERNAME_MIN_LEN {
+        base.insert_str(0, "user_");
+    }
+
+    let mut username = base.clone();
+    let mut suffix = 2;
+    while taken.contains(&username.to_lowercase()) {
+        username = format!("{base}{suffix}");
+        suffix += 1;
+    }
+    username
+}
+
+fn summary_section(templates: &Templates, title: &str, entries: &[String]) -> String {
+    let mut lines = vec![templates.render(title, &[("count", &entries.len())])];
+    lines.extend(
+        entries
+            .iter()
+            .take(MAX_LISTED)
+            .map(|entry| format!("- {entry}")),
+    );
+    if entries.len() > MAX_LISTED {
+        let more = templates.render(
+            &templates.and_more,
+            &[("count", &(entries.len() - MAX_LISTED))],
+        );
+        lines.push(format!("- {more}"));
+    }
+    lines.join("\n")
+}
+
+/// Create CTFNote accounts for every member of a Discord role who doesn't have one yet
+#[poise::command(slash_command, guild_only, check = "ctfnote_admin")]
+pub async fn ctfnote_provision_role(
+    ctx: Context<'_>,
+    #[description = "Members of this role get a CTFNote account"] role: Role,
+) -> Result<(), Error> {
+    let config = &ctx.data().config;
+    ctx.defer_ephemeral().await?;
+
+    let users = get_users(&config.ctfnote).await?;
+    let linked: HashSet<_> = users
+        .iter()
+        .filter_map(CtfnoteUser::discord_user_id)
+        .collect();
+    let mut taken: HashSet<String> = users
+        .iter()
+        .map(|user| user.username.to_lowercase())
+        .collect();
+
+    let (mut created, mut skipped, mut failed) = (Vec::new(), Vec::new(), Vec::new());
+    for member in members_with_role(ctx.http(), GuildId::new(config.guild_id), role.id).await? {
+        if linked.contains(&member.user.id) {
+            skipped.push(format!("<@{}>", member.user.id));
+            continue;
+        }
+
+        let username = unique_username(&member.user.name, &taken);
+        // Keep going on transport errors so the summary still covers everyone.
+        let outcome = register(&config.ctfnote, &username, member.user.id)
+            .await
+            .unwrap_or_else(|err| {
+                error!("Failed to register {} on CTFNote: {:?}", username, err);
+                AdminOutcome {
+                    success: false,
+                    message: format!("CTFNote request failed: {err}"),
+                }
+            });
+        ctx.data()
+            .audit
+            .record(
+                ctx.http(),
+                AuditEntry::new(
+                    ctx.author(),
+                    AuditAction::CreateAccount,
+                    format!("{} (<@{}>)", username, member.user.id),
+                )
+                .outcome(outcome.success, outcome.message.clone()),
+            )
+            .await;
+        if outcome.success {
+            taken.insert(username.to_lowercase());
+            created.push(format!("<@{}> as `{}`", member.user.id, username));
+        } else {
+            failed.push(format!("<@{}>: {}", member.user.id, outcome.message));
+        }
+    }
+    info!(
+        "{} provisioned CTFNote accounts for role {}: {} created, {} skipped, {} failed",
+        ctx.author().name,
+        role.name,
+        created.len(),
+        skipped.len(),
+        failed.len()
+    );
+
+    let templates = &config.templates;
+    let summary = [
+        summary_section(templates, &templates.provision_created, &created),
+        summary_section(templates, &templates.provision_skipped, &skipped),
+        summary_section(templates, &templates.provision_failed, &failed),
+    ]
+    .join("\n\n");
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .content(summary),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashSet, sync::Arc};
+
+use poise::{
+    serenity_prelude::{CreateAllowedMentions, Error, GuildId, Http, RoleId, User, UserId},
+    CreateReply,
+};
+use tracing::{error, info};
+
+use crate::{
+    audit::{AuditAction, AuditEntry, AuditLog},
+    checks::ctfnote_admin,
+    commands::provision::members_with_role,
+    ctfnote_api::{get_users, CtfnoteRole},
+    Config, Context, RoleSyncConfig,
+};
+
+// Keep the report below Discord's 2000 character message limit.
+const MAX_REPORTED_CHANGES: usize = 30;
+
+struct RoleChange {
+    user_id: UserId,
+    role_id: RoleId,
+    add: bool,
+}
+
+impl RoleSyncConfig {
+    const fn role_id(&self, role: CtfnoteRole) -> Option<u64> {
+        match role {
+            CtfnoteRole::Guest => self.guest_role_id,
+            CtfnoteRole::Member => self.member_role_id,
+            CtfnoteRole::Manager => self.manager_role_id,
+            CtfnoteRole::Admin => self.admin_role_id,
+        }
+    }
+}
+
+/// Work out which Discord roles need to be added or removed so that every linked guild member
+/// holds exactly the role configured for their CTFNote role, and members who aren't linked
+/// (anymore) hold none of them.
+async fn plan_role_sync(
+    http: &Http,
+    config: &Config,
+    sync: &RoleSyncConfig,
+) -> Result<Vec<RoleChange>, Error> {
+    let guild = GuildId::new(config.guild_id);
+    let mut changes = Vec::new();
+    let mut linked = HashSet::new();
+
+    for user in get_users(&config.ctfnote).await? {
+        let Some(user_id) = user.discord_user_id() else {
+            continue;
+        };
+        linked.insert(user_id);
+        let member = match guild.member(http, user_id).await {
+            Ok(member) => member,
+            // Linked to CTFNote but not (or no longer) in the guild.
+            Err(Error::Http(_)) => continue,
+            Err(err) => return Err(err),
+        };
+
+        for role in CtfnoteRole::ALL {
+            let Some(role_id) = sync.role_id(role).map(RoleId::new) else {
+                continue;
+            };
+            let wanted = user.role == role;
+            if wanted != member.roles.contains(&role_id) {
+                changes.push(RoleChange {
+                    user_id,
+                    role_id,
+                    add: wanted,
+                });
+            }
+        }
+    }
+
+    // E.g. members who ran /ctfnote_unlink or whose guest account expired.
+    for role in CtfnoteRole::ALL {
+        let Some(role_id) = sync.role_id(role).map(RoleId::new) else {
+            continue;
+        };
+        for member in members_with_role(http, guild, role_id).await? {
+            if !linked.contains(&member.user.id) {
+                changes.push(RoleChange {
+                    user_id: member.user.id,
+                    role_id,
+                    add: false,
+                });
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Apply `changes` on behalf of `actor`, or of the bot itself if it's `None`, auditing each one.
+async fn apply_role_changes(
+    http: &Http,
+    config: &Config,
+    audit: &AuditLog,
+    actor: Option<&User>,
+    changes: &[RoleChange],
+) -> Result<(), Error> {
+    let guild = GuildId::new(config.guild_id);
+    let reason = Some("CTFNote role sync");
+    for change in changes {
+        let (action, target, result) = if change.add {
+            (
+                AuditAction::GrantRole,
+                format!("<@&{}> to <@{}>", change.role_id, change.user_id),
+                http.add_member_role(guild, change.user_id, change.role_id, reason)
+                    .await,
+            )
+        } else {
+            (
+                AuditAction::RemoveRole,
+                format!("<@&{}> from <@{}>", change.role_id, change.user_id),
+                http.remove_member_role(guild, change.user_id, change.role_id, reason)
+                    .await,
+            )
+        };
+        let entry = actor.map_or_else(
+            || AuditEntry::automatic(action, target.clone()),
+            |actor| AuditEntry::new(actor, action, target.clone()),
+        );
+        let detail = result
+            .as_ref()
+            .map_or_else(ToString::to_string, |()| "CTFNote role sync".to_string());
+        audit
+            .record(http, entry.outcome(result.is_ok(), detail))
+            .await;
+        result?;
+    }
+    Ok(())
+}
+
+/// Sync Discord roles of linked members with their CTFNote roles
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    check = "ctfnote_admin"
+)]
+pub async fn ctfnote_sync_roles(
+    ctx: Context<'_>,
+    #[description = "Only report what would change (default: no)"] dry_run: Option<bool>,
+) -> Result<(), Error> {
+    let config = &ctx.data().config;
+    let templates = &config.templates;
+    let Some(sync) = &config.ctfnote.role_sync else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(templates.render(&templates.role_sync_disabled, &[])),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    ctx.defer_ephemeral().await?;
+    let dry_run = dry_run.unwrap_or(false);
+    let changes = plan_role_sync(ctx.http(), config, sync).await?;
+    if !dry_run {
+        apply_role_changes(
+            ctx.http(),
+            config,
+            &ctx.data().audit,
+            Some(ctx.author()),
+            &changes,
+        )
+        .await?;
+        info!(
+            "{} synced CTFNote roles ({} changes)",
+            ctx.author().name,
+            changes.len()
+        );
+    }
+
+    let mut report = vec![if changes.is_empty() {
+        templates.render(&templates.roles_in_sync, &[])
+    } else if dry_run {
+        templates.render(&templates.role_sync_dry_run, &[("count", &changes.len())])
+    } else {
+        templates.render(&templates.role_sync_done, &[("count", &changes.len())])
+    }];
+    report.extend(changes.iter().take(MAX_REPORTED_CHANGES).map(|change| {
+        let template = if change.add {
+            &templates.role_sync_add
+        } else {
+            &templates.role_sync_remove
+        };
+        templates.render(
+            template,
+            &[("role", &change.role_id), ("user", &change.user_id)],
+        )
+    }));
+    if changes.len() > MAX_REPORTED_CHANGES {
+        report.push(templates.render(
+            &templates.and_more,
+            &[("count", &(changes.len() - MAX_REPORTED_CHANGES))],
+        ));
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .content(report.join("\n")),
+    )
+    .await?;
+    Ok(())
+}
+
+pub fn role_sync_loop(config: Config, ctx: poise::serenity_prelude::Context, audit: Arc<AuditLog>) {
+    let Some(sync) = config.ctfnote.role_sync.clone() else {
+        return;
+    };
+    let Some(loop_seconds) = sync.loop_seconds else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(loop_seconds));
+        loop {
+            interval.tick().await;
+
+            let result = match plan_role_sync(&ctx.http, &config, &sync).await {
+                Ok(changes) => apply_role_changes(&ctx.http, &config, &audit, None, &changes)
+                    .await
+                    .map(|()| changes.len()),
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(0) => {}
+                Ok(count) => info!("CTFNote role sync made {} role changes", count),
+                Err(err) => error!("CTFNote role sync failed: {:?}", err),
+            }
+        }
+    });
+}
@@ -0,0 +1,227 @@
+use std::time::{Duration, Instant};
+
+use poise::{
+    serenity_prelude::{ChannelType, Error},
+    CreateReply,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ctfnote_api::{admin_post, get_ctf_tasks, get_current_ctfs},
+    Context, Data,
+};
+
+// Autocomplete runs on every keystroke, so reuse the task list for a little while.
+const TASK_TITLES_TTL: Duration = Duration::from_secs(30);
+
+pub struct CachedTaskTitles {
+    titles: Vec<String>,
+    fetched: Instant,
+}
+
+#[derive(Serialize)]
+struct TaskRequest {
+    discord_id: String,
+    task: String,
+}
+
+#[derive(Deserialize)]
+struct TaskResponse {
+    message: String,
+}
+
+#[derive(Serialize)]
+struct SolveTaskRequest {
+    discord_id: String,
+    task: String,
+    flag: String,
+}
+
+#[derive(Deserialize)]
+struct SolveTaskResponse {
+    solved: bool,
+    message: String,
+    task: Option<String>,
+    #[serde(default)]
+    solvers: Vec<String>,
+}
+
+/// Claim, unclaim or solve CTFNote tasks
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("claim", "unclaim", "solve"),
+    subcommand_required
+)]
+#[allow(clippy::unused_async)]
+pub async fn task(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Titles of the tasks of every running CTF, reusing lookups younger than `TASK_TITLES_TTL`.
+async fn task_titles(data: &Data) -> Vec<String> {
+    if let Some(cached) = &*data.task_titles.lock().await {
+        if cached.fetched.elapsed() < TASK_TITLES_TTL {
+            return cached.titles.clone();
+        }
+    }
+
+    let config = &data.config.ctfnote;
+    let Ok(ctfs) = get_current_ctfs(config).await else {
+        return Vec::new();
+    };
+    let mut titles = Vec::new();
+    for ctf in ctfs {
+        if let Ok(tasks) = get_ctf_tasks(config, ctf.id).await {
+            titles.extend(tasks.into_iter().map(|task| task.title));
+        }
+    }
+    *data.task_titles.lock().await = Some(CachedTaskTitles {
+        titles: titles.clone(),
+        fetched: Instant::now(),
+    });
+    titles
+}
+
+async fn autocomplete_task(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    task_titles(ctx.data())
+        .await
+        .into_iter()
+        .filter(|title| title.to_lowercase().contains(&partial))
+        .take(25) // Discord shows at most 25 choices
+        .collect()
+}
+
+/// Resolve the task a command acts on: the explicit argument if given, otherwise the name of
+/// the task thread the command was run in.
+async fn resolve_task(ctx: Context<'_>, task: Option<String>) -> Result<Option<String>, Error> {
+    if task.is_some() {
+        return Ok(task);
+    }
+
+    let thread = ctx.guild_channel().await.filter(|channel| {
+        matches!(
+            channel.kind,
+            ChannelType::PublicThread | ChannelType::PrivateThread
+        )
+    });
+    if thread.is_none() {
+        let templates = &ctx.data().config.templates;
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(templates.render(&templates.task_thread_only, &[])),
+        )
+        .await?;
+    }
+    Ok(thread.map(|thread| thread.name))
+}
+
+/// Claim a task on CTFNote
+#[poise::command(slash_command, guild_only)]
+pub async fn claim(
+    ctx: Context<'_>,
+    #[description = "Task name (defaults to the current task thread)"]
+    #[autocomplete = "autocomplete_task"]
+    task: Option<String>,
+) -> Result<(), Error> {
+    let Some(task) = resolve_task(ctx, task).await? else {
+        return Ok(());
+    };
+
+    let response: TaskResponse = admin_post(
+        &ctx.data().config.ctfnote,
+        "claim-task",
+        &TaskRequest {
+            discord_id: ctx.author().id.to_string(),
+            task,
+        },
+    )
+    .await?;
+    ctx.say(response.message).await?;
+    Ok(())
+}
+
+/// Stop working on a task on CTFNote
+#[poise::command(slash_command, guild_only)]
+pub async fn unclaim(
+    ctx: Context<'_>,
+    #[description = "Task name (defaults to the current task thread)"]
+    #[autocomplete = "autocomplete_task"]
+    task: Option<String>,
+) -> Result<(), Error> {
+    let Some(task) = resolve_task(ctx, task).await? else {
+        return Ok(());
+    };
+
+    let response: TaskResponse = admin_post(
+        &ctx.data().config.ctfnote,
+        "unclaim-task",
+        &TaskRequest {
+            discord_id: ctx.author().id.to_string(),
+            task,
+        },
+    )
+    .await?;
+    ctx.say(response.message).await?;
+    Ok(())
+}
+
+/// Mark a task as solved on CTFNote
+#[poise::command(slash_command, guild_only)]
+pub async fn solve(
+    ctx: Context<'_>,
+    #[description = "The flag"] flag: String,
+    #[description = "Task name (defaults to the current task thread)"]
+    #[autocomplete = "autocomplete_task"]
+    task: Option<String>,
+    #[description = "Hide the flag behind spoiler tags (default: yes)"] hide_flag: Option<bool>,
+) -> Result<(), Error> {
+    let Some(task) = resolve_task(ctx, task).await? else {
+        return Ok(());
+    };
+
+    let response: SolveTaskResponse = admin_post(
+        &ctx.data().config.ctfnote,
+        "solve-task",
+        &SolveTaskRequest {
+            discord_id: ctx.author().id.to_string(),
+            task: task.clone(),
+            flag: flag.clone(),
+        },
+    )
+    .await?;
+
+    if !response.solved {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(response.message),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let flag = if hide_flag.unwrap_or(true) {
+        format!("||`{flag}`||")
+    } else {
+        format!("`{flag}`")
+    };
+    let solvers = if response.solvers.is_empty() {
+        ctx.author().name.clone()
+    } else {
+        response.solvers.join(", ")
+    };
+    let templates = &ctx.data().config.templates;
+    ctx.say(templates.render(
+        &templates.task_solved,
+        &[
+            ("task", &response.task.unwrap_or(task)),
+            ("solvers", &solvers),
+            ("flag", &flag),
+        ],
+    ))
+    .await?;
+    Ok(())
+}
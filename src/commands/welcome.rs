@@ -1,57 +1,516 @@
-use crate::Context;
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    commands::ctfnote::{run_modal, Submission},
+    storage::JsonStore,
+    ApplicationContext, ChallengeConfig, Context, Data, WelcomeConfig,
+};
+use attempts::{FlagAttempts, Refusal};
+use chrono::Utc;
+use dynamic_flag::{artefact, dynamic_flag, flags_match};
+use flag_hash::verify_flag;
+use greeting::Greetings;
+use hints::hints_used;
 use poise::{
     command,
-    serenity_prelude::{GuildId, RoleId, Error},
+    serenity_prelude::{
+        ActionRowComponent, ChannelId, CreateActionRow, CreateAllowedMentions, CreateInputText,
+        CreateInteractionResponse, CreateMessage, CreateModal, Error, GuildId, Http,
+        InputTextStyle, Member, ModalInteractionData, RoleId, User, UserId,
+    },
+    CreateReply, Modal,
+};
+use rotation::{active_flag_hash, attempt_key, RotationState};
+use stats::Solve;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::Path,
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+pub mod attempts;
+pub mod dynamic_flag;
+pub mod flag_hash;
+pub mod greeting;
+pub mod hints;
+pub mod rotation;
+pub mod stats;
+
+/// Persistent state of the welcome challenges.
+pub struct WelcomeState {
+    attempts: FlagAttempts,
+    /// Members handed the artefact of each dynamic challenge.
+    issued_flags: JsonStore<HashMap<String, HashSet<u64>>>,
+    solves: JsonStore<Vec<Solve>>,
+    greetings: JsonStore<Greetings>,
+    /// Flag window last seen active, per rotating challenge.
+    active_windows: JsonStore<HashMap<String, RotationState>>,
+    /// Number of hints each member has unlocked, per challenge.
+    hints_used: JsonStore<HashMap<String, HashMap<u64, u32>>>,
+}
+
+impl WelcomeState {
+    pub fn open(data_dir: &Path) -> io::Result<Self> {
+        Ok(Self {
+            attempts: FlagAttempts::open(data_dir)?,
+            issued_flags: JsonStore::open(data_dir, "welcome_issued_flags.json")?,
+            solves: JsonStore::open(data_dir, "welcome_solves.json")?,
+            greetings: JsonStore::open(data_dir, "welcome_greetings.json")?,
+            active_windows: JsonStore::open(data_dir, "welcome_flag_rotation.json")?,
+            hints_used: JsonStore::open(data_dir, "welcome_hints_used.json")?,
+        })
+    }
+}
+
+fn find_challenge<'a>(data: &'a Data, id: &str) -> Option<&'a ChallengeConfig> {
+    data.config
+        .welcome
+        .challenges
+        .iter()
+        .find(|challenge| challenge.id.eq_ignore_ascii_case(id))
+}
+
+fn has_solved(member: &Member, challenge: &ChallengeConfig) -> bool {
+    member.roles.contains(&RoleId::new(challenge.role_id))
+}
+
+/// The challenge that must be solved before `challenge`, if the member hasn't solved it yet.
+fn missing_prerequisite<'a>(
+    data: &'a Data,
+    member: &Member,
+    challenge: &ChallengeConfig,
+) -> Option<&'a ChallengeConfig> {
+    challenge
+        .requires
+        .as_deref()
+        .and_then(|id| find_challenge(data, id))
+        .filter(|required| !has_solved(member, required))
+}
+
+/// Post a message in the welcome admin channel, if one is configured.
+async fn notify_admins(http: &Http, welcome: &WelcomeConfig, content: String) {
+    let Some(channel_id) = welcome.alert_channel_id else {
+        return;
+    };
+    let message = CreateMessage::new()
+        .allowed_mentions(CreateAllowedMentions::new())
+        .content(content);
+    if let Err(err) = ChannelId::new(channel_id).send_message(http, message).await {
+        error!("Failed to notify the welcome admins: {:?}", err);
+    }
+}
 
+/// Tell the admins about suspicious submissions, e.g. brute-forcing or a shared flag.
+async fn alert_admins(http: &Http, data: &Data, alert: String) {
+    warn!("welcome alert: {}", alert);
+    notify_admins(http, &data.config.welcome, alert).await;
+}
+
+enum FlagCheck {
+    Correct,
+    Wrong,
+    /// The personal flag of another member of a dynamic challenge.
+    Shared(UserId),
+}
+
+/// The personal flag of `user` for a dynamic challenge.
+fn personal_flag(data: &Data, challenge: &ChallengeConfig, user: UserId) -> Option<String> {
+    let welcome = &data.config.welcome;
+    let Some(secret) = welcome.flag_secret.as_deref() else {
+        error!(
+            "welcome challenge {} has a dynamic flag but WELCOME_FLAG_SECRET isn't set",
+            challenge.id
+        );
+        return None;
+    };
+    Some(dynamic_flag(
+        secret,
+        &welcome.flag_prefix,
+        &challenge.id,
+        user,
+    ))
+}
+
+async fn check_flag(
+    data: &Data,
+    challenge: &ChallengeConfig,
+    user: UserId,
+    flag: &str,
+) -> FlagCheck {
+    if !challenge.dynamic_flag {
+        return match active_flag_hash(challenge) {
+            Some(flag_hash) if verify_flag(flag, flag_hash) => FlagCheck::Correct,
+            Some(_) => FlagCheck::Wrong,
+            None => {
+                error!("welcome challenge {} has no active flag", challenge.id);
+                FlagCheck::Wrong
+            }
+        };
+    }
 
+    if personal_flag(data, challenge, user).is_some_and(|expected| flags_match(flag, &expected)) {
+        return FlagCheck::Correct;
+    }
+    // Only members who were handed an artefact can have leaked their flag.
+    let issued = data
+        .welcome_state
+        .issued_flags
+        .read(|issued| issued.get(&challenge.id).cloned().unwrap_or_default())
+        .await;
+    issued
+        .into_iter()
+        .map(UserId::new)
+        .filter(|&owner| owner != user)
+        .find(|&owner| {
+            personal_flag(data, challenge, owner)
+                .is_some_and(|expected| flags_match(flag, &expected))
+        })
+        .map_or(FlagCheck::Wrong, FlagCheck::Shared)
+}
+
+/// Count a wrong flag, alerting the admins if needed, and return the reply for the user.
+async fn reject_flag(
+    http: &Http,
+    data: &Data,
+    user: &User,
+    challenge: &ChallengeConfig,
+    check: FlagCheck,
+) -> Result<Submission, Error> {
+    let templates = &data.config.templates;
+    let rate_limit = &data.config.welcome.rate_limit;
+    let wrong = data
+        .welcome_state
+        .attempts
+        .record_wrong(rate_limit, user.id, &attempt_key(challenge))
+        .await?;
+    if wrong.alert {
+        alert_admins(
+            http,
+            data,
+            templates.render(
+                &templates.brute_force_alert,
+                &[
+                    ("user", &user.id),
+                    ("name", &user.name),
+                    ("count", &wrong.count),
+                    ("challenge", &challenge.id),
+                ],
+            ),
+        )
+        .await;
+    }
+    if let FlagCheck::Shared(owner) = check {
+        alert_admins(
+            http,
+            data,
+            templates.render(
+                &templates.shared_flag_alert,
+                &[
+                    ("user", &user.id),
+                    ("name", &user.name),
+                    ("challenge", &challenge.id),
+                    ("owner", &owner),
+                ],
+            ),
+        )
+        .await;
+        return Ok(Submission::Rejected(
+            templates.render(&templates.shared_flag, &[]),
+        ));
+    }
+    let retry = wrong.locked_until.map_or_else(String::new, |until| {
+        templates.render(
+            &templates.retry_after,
+            &[("time", &format!("<t:{}:R>", until.timestamp()))],
+        )
+    });
+    Ok(Submission::Rejected(
+        templates.render(&templates.wrong_flag, &[("retry", &retry)]),
+    ))
+}
+
+/// The guild member submitting a flag, or the reply for them if they can't be found.
+async fn guild_member(http: &Http, data: &Data, user: &User) -> Result<Member, Submission> {
+    let templates = &data.config.templates;
+    match GuildId::new(data.config.guild_id)
+        .member(http, user.id)
+        .await
+    {
+        Ok(member) => Ok(member),
+        Err(Error::Http(_)) => {
+            info!("non-member {} attempted `flag` command.", user.name);
+            Err(Submission::Rejected(
+                templates.render(&templates.join_server, &[]),
+            ))
+        }
+        Err(err) => {
+            error!("flag command member retrieval failed {:?}!", err);
+            Err(Submission::Rejected(
+                templates.render(&templates.contact_admin, &[]),
+            ))
+        }
+    }
+}
+
+/// Check a flag for a welcome challenge and grant its role, returning the reply for the user.
+pub async fn submit_flag(
+    http: &Http,
+    data: &Data,
+    user: &User,
+    challenge_id: &str,
+    flag: &str,
+) -> Result<Submission, Error> {
+    let templates = &data.config.templates;
+    let Some(challenge) = find_challenge(data, challenge_id) else {
+        return Ok(Submission::Rejected(templates.render(
+            &templates.unknown_challenge,
+            &[("challenge", &challenge_id)],
+        )));
+    };
+
+    let member = match guild_member(http, data, user).await {
+        Ok(member) => member,
+        Err(rejection) => return Ok(rejection),
+    };
+
+    if has_solved(&member, challenge) {
+        return Ok(Submission::Rejected(
+            templates.render(&templates.already_solved, &[("role", &challenge.name)]),
+        ));
+    }
+    if let Some(required) = missing_prerequisite(data, &member, challenge) {
+        return Ok(Submission::Rejected(templates.render(
+            &templates.requires_challenge,
+            &[("required", &required.id), ("challenge", &challenge.id)],
+        )));
+    }
+
+    let rate_limit = &data.config.welcome.rate_limit;
+    match data.welcome_state.attempts.check(rate_limit, user.id).await {
+        Ok(()) => {}
+        Err(Refusal::Global) => {
+            return Ok(Submission::Rejected(
+                templates.render(&templates.too_many_flags, &[]),
+            ));
+        }
+        Err(Refusal::Cooldown(until)) => {
+            return Ok(Submission::Rejected(templates.render(
+                &templates.flag_cooldown,
+                &[("time", &format!("<t:{}:R>", until.timestamp()))],
+            )));
+        }
+    }
+
+    let check = check_flag(data, challenge, user.id, flag).await;
+    if !matches!(check, FlagCheck::Correct) {
+        return reject_flag(http, data, user, challenge, check).await;
+    }
+
+    let attempts = data
+        .welcome_state
+        .attempts
+        .wrong_attempts(user.id, &attempt_key(challenge))
+        .await
+        + 1;
+    let hints = hints_used(data, user.id, &attempt_key(challenge)).await;
+    member
+        .add_role(http, RoleId::new(challenge.role_id))
+        .await?;
+    data.welcome_state
+        .solves
+        .update(|solves| {
+            solves.push(Solve {
+                user_id: user.id.get(),
+                challenge: challenge.id.clone(),
+                time: Utc::now(),
+                attempts,
+                hints,
+            });
+        })
+        .await?;
+    data.audit
+        .record(
+            http,
+            AuditEntry::new(
+                user,
+                AuditAction::GrantRole,
+                format!("\"{}\" to <@{}>", challenge.name, user.id),
+            )
+            .outcome(
+                true,
+                format!(
+                    "solved `{}` in {} attempts with {} hints",
+                    challenge.id, attempts, hints
+                ),
+            ),
+        )
+        .await;
+    Ok(Submission::Accepted(templates.render(
+        &templates.flag_solved,
+        &[
+            ("role", &challenge.name),
+            ("user", &format!("<@{}>", user.id)),
+        ],
+    )))
+}
+
+/// Submit the flag of a welcome challenge, e.g. `!flag welcome sigint{...}`
 #[command(prefix_command, dm_only)]
-pub async fn welcome(
+pub async fn flag(
     ctx: Context<'_>,
+    #[description = "challenge"] challenge: String,
     #[description = "flag"] flag: String,
 ) -> std::result::Result<(), Error> {
-    let config = &ctx.data().config;
-    let sigint_guild = GuildId::new(config.guild_id);
-    let welcome_role = RoleId::new(config.welcome.role_id);
-
-    let message = if ctx
-        .author()
-        .has_role(&ctx.http(), sigint_guild, welcome_role)
-        .await?
-    {
-        "You already have the \"Curious Hacker\" role."
-    } else if flag == config.welcome.flag {
-        // Add role to the person DM
-        match sigint_guild
-            .member(&ctx.http(), ctx.author().id)
-            .await
-        {
-            Ok(member) => {
-                member.add_role(&ctx.http(), welcome_role).await?;
-                info!(
-                    "awarded \"Curious Hacker\" role to {}.",
-                    ctx.author().name
-                );
-
-                "Congratulations! You have earned the \"Curious Hacker\" role!"
-            }
-            Err(Error::Http(_)) => {
-                info!(
-                    "non-member {} attempted `welcome` command.",
-                    ctx.author().name
-                );
-                "Please join the SIGINT server first! https://discord.gg/WynY7FD3HP"
+    let (Submission::Accepted(message) | Submission::Rejected(message)) =
+        submit_flag(ctx.http(), ctx.data(), ctx.author(), &challenge, &flag).await?;
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// The flag form of `/welcome`. It isn't derived so the placeholder can come from the templates.
+#[derive(Clone)]
+struct FlagModal {
+    flag: String,
+    /// Only shown while the flag is empty, so it isn't submitted back.
+    placeholder: String,
+}
+
+impl Modal for FlagModal {
+    fn create(defaults: Option<Self>, custom_id: String) -> CreateInteractionResponse {
+        let mut input = CreateInputText::new(InputTextStyle::Short, "Flag", "flag");
+        if let Some(defaults) = defaults {
+            if !defaults.flag.is_empty() {
+                input = input.value(defaults.flag);
             }
-            err => {
-                error!("welcome command member retrieval failed {:?}!", err);
-                "An error has occurred, please contact SIGINT admin"
+            if !defaults.placeholder.is_empty() {
+                input = input.placeholder(defaults.placeholder);
             }
         }
-    } else {
-        "I don't think that is the right flag... Try harder!"
+        CreateInteractionResponse::Modal(
+            CreateModal::new(custom_id, "Submit a welcome flag")
+                .components(vec![CreateActionRow::InputText(input)]),
+        )
+    }
+
+    fn parse(data: ModalInteractionData) -> Result<Self, &'static str> {
+        let flag = data
+            .components
+            .into_iter()
+            .flat_map(|row| row.components)
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) if input.custom_id == "flag" => input.value,
+                _ => None,
+            })
+            .ok_or("flag missing from the modal submission")?;
+        Ok(Self {
+            flag,
+            placeholder: String::new(),
+        })
+    }
+}
+
+#[allow(clippy::unused_async)]
+async fn autocomplete_challenge(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    ctx.data()
+        .config
+        .welcome
+        .challenges
+        .iter()
+        .filter(|challenge| challenge.id.to_lowercase().contains(&partial))
+        .take(25) // Discord shows at most 25 choices
+        .map(|challenge| challenge.id.clone())
+        .collect()
+}
+
+/// Submit the flag of a welcome challenge privately
+#[command(slash_command, guild_only)]
+pub async fn welcome(
+    ctx: ApplicationContext<'_>,
+    #[description = "The challenge you solved"]
+    #[autocomplete = "autocomplete_challenge"]
+    challenge: String,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let user = &ctx.interaction.user;
+    let challenge = &challenge;
+    let templates = &data.config.templates;
+    let defaults = FlagModal {
+        flag: String::new(),
+        placeholder: templates.render(
+            &templates.flag_placeholder,
+            &[("prefix", &data.config.welcome.flag_prefix)],
+        ),
     };
+    run_modal(ctx, Some(defaults), |modal: FlagModal| async move {
+        submit_flag(
+            &ctx.serenity_context.http,
+            data,
+            user,
+            challenge,
+            modal.flag.trim(),
+        )
+        .await
+    })
+    .await
+}
 
-    ctx.say(message).await?;
+/// List the welcome challenges and the ones you have solved
+#[command(slash_command, guild_only)]
+pub async fn challenges(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let member = GuildId::new(data.config.guild_id)
+        .member(ctx, ctx.author().id)
+        .await?;
+
+    let templates = &data.config.templates;
+    let mut lines = vec![
+        templates.render(&templates.challenges_intro, &[]),
+        String::new(),
+    ];
+    for challenge in &data.config.welcome.challenges {
+        let description = if challenge.dynamic_flag {
+            let Some(flag) = personal_flag(data, challenge, ctx.author().id) else {
+                continue;
+            };
+            data.welcome_state
+                .issued_flags
+                .update(|issued| {
+                    issued
+                        .entry(challenge.id.clone())
+                        .or_default()
+                        .insert(ctx.author().id.get())
+                })
+                .await?;
+            challenge
+                .description
+                .replace("{artefact}", &artefact(&flag))
+        } else {
+            challenge.description.clone()
+        };
+        let status = if has_solved(&member, challenge) {
+            templates.render(&templates.challenge_solved, &[])
+        } else if let Some(required) = missing_prerequisite(data, &member, challenge) {
+            templates.render(&templates.challenge_locked, &[("required", &required.id)])
+        } else {
+            templates.render(&templates.challenge_open, &[])
+        };
+        lines.push(templates.render(
+            &templates.challenge_line,
+            &[
+                ("status", &status),
+                ("challenge", &challenge.id),
+                ("role", &challenge.name),
+                ("description", &description),
+            ],
+        ));
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(lines.join("\n")),
+    )
+    .await?;
     Ok(())
 }
@@ -0,0 +1,146 @@
+//! Persistent wrong-flag counts and the rate limits built on them.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use chrono::{serde::ts_seconds_option, DateTime, TimeZone, Utc};
+use poise::serenity_prelude::{futures::lock::Mutex, UserId};
+use serde::{Deserialize, Serialize};
+
+use crate::{storage::JsonStore, RateLimitConfig};
+
+const GLOBAL_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Default)]
+struct UserAttempts {
+    /// Wrong submissions per challenge id.
+    wrong: HashMap<String, u32>,
+    #[serde(with = "ts_seconds_option")]
+    locked_until: Option<DateTime<Utc>>,
+    /// Unix time of the first wrong submission per challenge id.
+    #[serde(default)]
+    first_wrong: HashMap<String, i64>,
+}
+
+pub enum Refusal {
+    /// Everyone together is submitting more than `global_attempts_per_minute`.
+    Global,
+    /// This user is cooling down after too many wrong flags.
+    Cooldown(DateTime<Utc>),
+}
+
+pub struct WrongAttempt {
+    pub count: u32,
+    pub locked_until: Option<DateTime<Utc>>,
+    /// Whether this attempt reached `alert_threshold` for the challenge.
+    pub alert: bool,
+}
+
+pub struct FlagAttempts {
+    users: JsonStore<HashMap<u64, UserAttempts>>,
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+impl FlagAttempts {
+    pub fn open(data_dir: &Path) -> io::Result<Self> {
+        Ok(Self {
+            users: JsonStore::open(data_dir, "welcome_attempts.json")?,
+            recent: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Check whether `user` may submit a flag now, counting the submission towards the global
+    /// limit if so.
+    pub async fn check(&self, config: &RateLimitConfig, user: UserId) -> Result<(), Refusal> {
+        let locked_until = self
+            .users
+            .read(|users| users.get(&user.get()).and_then(|user| user.locked_until))
+            .await;
+        if let Some(locked_until) = locked_until.filter(|&until| until > Utc::now()) {
+            return Err(Refusal::Cooldown(locked_until));
+        }
+
+        let mut recent = self.recent.lock().await;
+        while recent
+            .front()
+            .is_some_and(|submitted| submitted.elapsed() > GLOBAL_WINDOW)
+        {
+            recent.pop_front();
+        }
+        if recent.len() >= config.global_attempts_per_minute {
+            return Err(Refusal::Global);
+        }
+        recent.push_back(Instant::now());
+        Ok(())
+    }
+
+    /// Count a wrong flag and start the user's cooldown once they are past `free_attempts`. The
+    /// cooldown doubles with every further wrong flag, up to `max_cooldown_seconds`.
+    pub async fn record_wrong(
+        &self,
+        config: &RateLimitConfig,
+        user: UserId,
+        challenge: &str,
+    ) -> io::Result<WrongAttempt> {
+        self.users
+            .update(|users| {
+                let attempts = users.entry(user.get()).or_default();
+                let count = attempts.wrong.entry(challenge.to_string()).or_default();
+                *count += 1;
+                let count = *count;
+                attempts
+                    .first_wrong
+                    .entry(challenge.to_string())
+                    .or_insert_with(|| Utc::now().timestamp());
+
+                let locked_until = count.checked_sub(config.free_attempts).map(|excess| {
+                    let seconds = config
+                        .cooldown_seconds
+                        .saturating_mul(2_u32.saturating_pow(excess))
+                        .min(config.max_cooldown_seconds);
+                    Utc::now() + chrono::Duration::seconds(i64::from(seconds))
+                });
+                attempts.locked_until = locked_until;
+
+                WrongAttempt {
+                    count,
+                    locked_until,
+                    alert: count == config.alert_threshold,
+                }
+            })
+            .await
+    }
+
+    /// Number of wrong flags `user` has submitted for `challenge`.
+    pub async fn wrong_attempts(&self, user: UserId, challenge: &str) -> u32 {
+        self.users
+            .read(|users| {
+                users
+                    .get(&user.get())
+                    .and_then(|user| user.wrong.get(challenge))
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .await
+    }
+
+    /// When `user` first submitted a wrong flag for `challenge`.
+    pub async fn first_wrong_attempt(
+        &self,
+        user: UserId,
+        challenge: &str,
+    ) -> Option<DateTime<Utc>> {
+        self.users
+            .read(|users| {
+                users
+                    .get(&user.get())
+                    .and_then(|user| user.first_wrong.get(challenge))
+                    .and_then(|&time| Utc.timestamp_opt(time, 0).single())
+            })
+            .await
+    }
+}
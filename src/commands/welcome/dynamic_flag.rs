@@ -0,0 +1,29 @@
+//! Per-user flags derived from a server secret, so a flag shared in a group chat only works for
+//! the member it was made for.
+
+use hmac::{Hmac, Mac};
+use poise::serenity_prelude::UserId;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+// Hex characters of the HMAC kept in the flag.
+const FLAG_HMAC_LEN: usize = 16;
+
+/// The flag `user` must submit for `challenge`, e.g. `sigint{0123456789abcdef}`.
+pub fn dynamic_flag(secret: &str, prefix: &str, challenge: &str, user: UserId) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{challenge}:{user}").as_bytes());
+    let digest = hex::encode(mac.finalize().into_bytes());
+    format!("{}{{{}}}", prefix, &digest[..FLAG_HMAC_LEN])
+}
+
+/// The personalised artefact handed to `user`, substituted for `{artefact}` in the challenge
+/// description. Recovering the flag from it is the challenge.
+pub fn artefact(flag: &str) -> String {
+    hex::encode(flag)
+}
+
+pub fn flags_match(submitted: &str, expected: &str) -> bool {
+    submitted.as_bytes().ct_eq(expected.as_bytes()).into()
+}
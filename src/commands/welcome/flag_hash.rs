@@ -0,0 +1,46 @@
+//! Welcome flags are configured as argon2 hashes so `config.toml`, and the image built from it,
+//! never contains the real flags.
+
+use std::io::{self, BufRead};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use tracing::error;
+
+/// Hash a flag with a random salt, in the PHC string format expected by `flag_hash`.
+pub fn hash_flag(flag: &str) -> argon2::password_hash::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(flag.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// Check a submitted flag against a configured hash. The comparison is constant-time.
+pub fn verify_flag(flag: &str, flag_hash: &str) -> bool {
+    match PasswordHash::new(flag_hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(flag.as_bytes(), &hash)
+            .is_ok(),
+        Err(err) => {
+            error!("Invalid welcome flag_hash in config.toml: {}", err);
+            false
+        }
+    }
+}
+
+/// `sigint-bot hash-flag [flag]` prints the hash of a flag, read from stdin if it isn't given so
+/// it stays out of the shell history.
+pub fn hash_flag_command(flag: Option<String>) {
+    let flag = flag.unwrap_or_else(|| {
+        eprintln!("Enter the flag:");
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .expect("Error reading the flag from stdin");
+        line.trim_end_matches(['\r', '\n']).to_string()
+    });
+    println!("{}", hash_flag(&flag).expect("Error hashing the flag"));
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, CreateAllowedMentions, CreateMessage, Member,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::Data;
+
+/// A new member who was sent the welcome greeting.
+#[derive(Serialize, Deserialize)]
+pub struct Greeting {
+    #[serde(with = "ts_seconds")]
+    pub time: DateTime<Utc>,
+    /// Whether the greeting was a DM rather than a post in the welcome channel.
+    pub dm: bool,
+}
+
+pub type Greetings = HashMap<u64, Greeting>;
+
+/// DM the welcome greeting to a new member, falling back to the welcome channel if their DMs are
+/// closed.
+pub async fn greet_member(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> Result<(), serenity::Error> {
+    let welcome = &data.config.welcome;
+    let Some(greeting) = &welcome.greeting else {
+        return Ok(());
+    };
+    if member.user.bot || member.guild_id.get() != data.config.guild_id {
+        return Ok(());
+    }
+
+    let content = data
+        .config
+        .templates
+        .render(greeting, &[("user", &format!("<@{}>", member.user.id))]);
+    let dm = member
+        .user
+        .direct_message(ctx, CreateMessage::new().content(&content))
+        .await
+        .is_ok();
+    if !dm {
+        let Some(channel_id) = welcome.welcome_channel_id else {
+            warn!("couldn't DM the welcome greeting to {}", member.user.name);
+            return Ok(());
+        };
+        ChannelId::new(channel_id)
+            .send_message(
+                ctx,
+                CreateMessage::new()
+                    .allowed_mentions(CreateAllowedMentions::new().users(vec![member.user.id]))
+                    .content(content),
+            )
+            .await?;
+    }
+    info!("greeted new member {}", member.user.name);
+
+    data.welcome_state
+        .greetings
+        .update(|greetings| {
+            greetings.insert(
+                member.user.id.get(),
+                Greeting {
+                    time: Utc::now(),
+                    dm,
+                },
+            )
+        })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Hints that unlock after a number of wrong flags or some time after the first wrong flag.
+
+use chrono::{DateTime, Duration, Utc};
+use poise::{
+    command,
+    serenity_prelude::{Error, UserId},
+    CreateReply,
+};
+
+use super::{attempt_key, autocomplete_challenge, find_challenge};
+use crate::{templates::Templates, Context, Data, HintConfig};
+
+impl HintConfig {
+    /// When the hint unlocks by time, if it does and the first wrong flag has been submitted.
+    fn unlocks_at(&self, first_wrong: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        let minutes = self.after_minutes?;
+        first_wrong.map(|first| first + Duration::minutes(i64::from(minutes)))
+    }
+
+    fn is_unlocked(&self, wrong: u32, first_wrong: Option<DateTime<Utc>>) -> bool {
+        if self.after_attempts.is_none() && self.after_minutes.is_none() {
+            return true;
+        }
+        self.after_attempts.is_some_and(|after| wrong >= after)
+            || self
+                .unlocks_at(first_wrong)
+                .is_some_and(|at| at <= Utc::now())
+    }
+
+    /// How the user can unlock the hint.
+    fn requirement(
+        &self,
+        templates: &Templates,
+        wrong: u32,
+        first_wrong: Option<DateTime<Utc>>,
+    ) -> String {
+        let mut ways = Vec::new();
+        if let Some(after) = self.after_attempts {
+            ways.push(templates.render(
+                &templates.hint_after_attempts,
+                &[("count", &after.saturating_sub(wrong))],
+            ));
+        }
+        match (self.after_minutes, self.unlocks_at(first_wrong)) {
+            (_, Some(at)) => ways.push(templates.render(
+                &templates.hint_at,
+                &[("time", &format!("<t:{}:R>", at.timestamp()))],
+            )),
+            (Some(minutes), None) => {
+                ways.push(
+                    templates.render(&templates.hint_after_minutes, &[("minutes", &minutes)]),
+                );
+            }
+            (None, None) => {}
+        }
+        ways.join(&templates.render(&templates.hint_requirement_separator, &[]))
+    }
+}
+
+/// Number of hints `user` has unlocked under the attempt key `challenge`.
+pub async fn hints_used(data: &Data, user: UserId, challenge: &str) -> u32 {
+    data.welcome_state
+        .hints_used
+        .read(|used| {
+            used.get(challenge)
+                .and_then(|users| users.get(&user.get()))
+                .copied()
+                .unwrap_or_default()
+        })
+        .await
+}
+
+/// Show the hints you have unlocked for a welcome challenge
+#[command(slash_command, guild_only)]
+pub async fn hint(
+    ctx: Context<'_>,
+    #[description = "The challenge you are stuck on"]
+    #[autocomplete = "autocomplete_challenge"]
+    challenge: String,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let templates = &data.config.templates;
+    let user = ctx.author().id;
+    let Some(challenge) = find_challenge(data, &challenge) else {
+        ctx.send(
+            CreateReply::default().ephemeral(true).content(
+                templates.render(&templates.unknown_challenge, &[("challenge", &challenge)]),
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+    if challenge.hints.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(templates.render(&templates.no_hints, &[("challenge", &challenge.id)])),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let key = attempt_key(challenge);
+    let attempts = &data.welcome_state.attempts;
+    let wrong = attempts.wrong_attempts(user, &key).await;
+    let first_wrong = attempts.first_wrong_attempt(user, &key).await;
+
+    let mut lines = vec![templates.render(&templates.hints_title, &[("challenge", &challenge.id)])];
+    let mut unlocked = 0;
+    for (number, hint) in (1..).zip(&challenge.hints) {
+        if hint.is_unlocked(wrong, first_wrong) {
+            lines.push(templates.render(
+                &templates.hint_unlocked,
+                &[("number", &number), ("hint", &hint.text)],
+            ));
+            unlocked += 1;
+        } else {
+            lines.push(templates.render(
+                &templates.hint_locked,
+                &[
+                    ("number", &number),
+                    (
+                        "requirement",
+                        &hint.requirement(templates, wrong, first_wrong),
+                    ),
+                ],
+            ));
+        }
+    }
+
+    if unlocked > 0 {
+        data.welcome_state
+            .hints_used
+            .update(|used| {
+                let count = used.entry(key).or_default().entry(user.get()).or_default();
+                *count = (*count).max(unlocked);
+            })
+            .await?;
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(lines.join("\n")),
+    )
+    .await?;
+    Ok(())
+}
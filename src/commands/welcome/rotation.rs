@@ -0,0 +1,264 @@
+//! Challenges with a list of `flags` only accept the one whose validity window is open, e.g. one
+//! per semester, and can take their role back from members who haven't solved the new flag.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use poise::serenity_prelude::{self as serenity, Error, GuildId, Http, RoleId};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use super::{notify_admins, WelcomeState};
+use crate::{
+    audit::{AuditAction, AuditEntry, AuditLog},
+    commands::provision::members_with_role,
+    ChallengeConfig, Config, FlagWindow,
+};
+
+const ROTATION_CHECK: Duration = Duration::from_secs(300);
+
+/// What happens to members holding a challenge's role when its flag rotates and they haven't
+/// solved the new one.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationPolicy {
+    /// They keep the role.
+    #[default]
+    Keep,
+    /// The role is removed until they solve the new flag.
+    Revoke,
+    /// The role is swapped for `veteran_role_id`.
+    Veteran,
+}
+
+/// What the rotation loop last saw of a challenge with `flags`.
+#[derive(Serialize, Deserialize)]
+pub struct RotationState {
+    /// Unix time the last window seen open started at.
+    window: i64,
+    /// Whether the admins were told that no window is open anymore.
+    lapsed: bool,
+}
+
+/// The flag window of `challenge` that is open right now. When several are, the latest one wins.
+fn active_window(challenge: &ChallengeConfig) -> Option<&FlagWindow> {
+    let now = Utc::now();
+    challenge
+        .flags
+        .iter()
+        .filter(|window| {
+            window.valid_from <= now && window.valid_until.map_or(true, |until| now < until)
+        })
+        .max_by_key(|window| window.valid_from)
+}
+
+/// The key wrong attempts and hints of `challenge` are counted under, so they start over when
+/// its flag rotates.
+pub fn attempt_key(challenge: &ChallengeConfig) -> String {
+    active_window(challenge).map_or_else(
+        || challenge.id.clone(),
+        |window| format!("{}@{}", challenge.id, window.valid_from.timestamp()),
+    )
+}
+
+/// The hash of the flag `challenge` accepts right now.
+pub fn active_flag_hash(challenge: &ChallengeConfig) -> Option<&str> {
+    if challenge.flags.is_empty() {
+        challenge.flag_hash.as_deref()
+    } else {
+        active_window(challenge).map(|window| window.flag_hash.as_str())
+    }
+}
+
+/// Apply the rotation policy of `challenge` to the members who haven't solved it since
+/// `window`, returning how many were affected. Members are only handled once their role is
+/// removed, so running it again after an error picks up where it stopped.
+async fn apply_policy(
+    http: &Http,
+    config: &Config,
+    state: &WelcomeState,
+    audit: &AuditLog,
+    challenge: &ChallengeConfig,
+    window: &FlagWindow,
+) -> Result<usize, Error> {
+    let veteran_role = match (challenge.on_rotation, challenge.veteran_role_id) {
+        (RotationPolicy::Keep, _) => return Ok(0),
+        (RotationPolicy::Revoke, _) => None,
+        (RotationPolicy::Veteran, Some(veteran_role_id)) => Some(RoleId::new(veteran_role_id)),
+        (RotationPolicy::Veteran, None) => {
+            error!(
+                "welcome challenge {} archives to a veteran role but has no veteran_role_id",
+                challenge.id
+            );
+            return Ok(0);
+        }
+    };
+
+    let resolved: HashSet<u64> = state
+        .solves
+        .read(|solves| {
+            solves
+                .iter()
+                .filter(|solve| solve.challenge == challenge.id && solve.time >= window.valid_from)
+                .map(|solve| solve.user_id)
+                .collect()
+        })
+        .await;
+    let role = RoleId::new(challenge.role_id);
+    let mut affected = 0;
+    for member in members_with_role(http, GuildId::new(config.guild_id), role).await? {
+        if resolved.contains(&member.user.id.get()) {
+            continue;
+        }
+        if let Some(veteran_role) = veteran_role {
+            member.add_role(http, veteran_role).await?;
+            audit
+                .record(
+                    http,
+                    AuditEntry::automatic(
+                        AuditAction::GrantRole,
+                        format!("veteran role to <@{}>", member.user.id),
+                    )
+                    .outcome(true, format!("`{}` flag rotated", challenge.id)),
+                )
+                .await;
+        }
+        member.remove_role(http, role).await?;
+        audit
+            .record(
+                http,
+                AuditEntry::automatic(
+                    AuditAction::RemoveRole,
+                    format!("\"{}\" from <@{}>", challenge.name, member.user.id),
+                )
+                .outcome(true, format!("`{}` flag rotated", challenge.id)),
+            )
+            .await;
+        affected += 1;
+    }
+    Ok(affected)
+}
+
+async fn rotate_flags(
+    http: &Http,
+    config: &Config,
+    state: &WelcomeState,
+    audit: &AuditLog,
+) -> Result<(), Error> {
+    let templates = &config.templates;
+    for challenge in &config.welcome.challenges {
+        if challenge.flags.is_empty() {
+            continue;
+        }
+        let previous = state
+            .active_windows
+            .read(|windows| {
+                windows
+                    .get(&challenge.id)
+                    .map(|seen| (seen.window, seen.lapsed))
+            })
+            .await;
+        let Some(window) = active_window(challenge) else {
+            // Keep the last window so the policy still applies when the next one opens.
+            if let Some((seen, false)) = previous {
+                state
+                    .active_windows
+                    .update(|windows| {
+                        windows.insert(
+                            challenge.id.clone(),
+                            RotationState {
+                                window: seen,
+                                lapsed: true,
+                            },
+                        )
+                    })
+                    .await?;
+                notify_admins(
+                    http,
+                    &config.welcome,
+                    templates.render(
+                        &templates.flag_lapsed_alert,
+                        &[("challenge", &challenge.id)],
+                    ),
+                )
+                .await;
+            }
+            continue;
+        };
+        let active = window.valid_from.timestamp();
+        if previous.is_some_and(|(seen, _)| seen == active) {
+            continue;
+        }
+
+        // Don't punish anyone the first time the bot sees a challenge's flags.
+        let affected = if previous.is_some() {
+            apply_policy(http, config, state, audit, challenge, window).await?
+        } else {
+            0
+        };
+        // Only remember the window once the policy went through, so errors are retried.
+        state
+            .active_windows
+            .update(|windows| {
+                windows.insert(
+                    challenge.id.clone(),
+                    RotationState {
+                        window: active,
+                        lapsed: false,
+                    },
+                )
+            })
+            .await?;
+        info!(
+            "welcome challenge {} rotated to the flag valid from {}",
+            challenge.id, window.valid_from
+        );
+        let until = window.valid_until.map_or_else(String::new, |until| {
+            templates.render(
+                &templates.flag_rotated_until,
+                &[("time", &format!("<t:{}:f>", until.timestamp()))],
+            )
+        });
+        let policy = match challenge.on_rotation {
+            RotationPolicy::Keep => String::new(),
+            RotationPolicy::Revoke => {
+                templates.render(&templates.flag_rotated_revoked, &[("count", &affected)])
+            }
+            RotationPolicy::Veteran => {
+                templates.render(&templates.flag_rotated_veteran, &[("count", &affected)])
+            }
+        };
+        notify_admins(
+            http,
+            &config.welcome,
+            templates.render(
+                &templates.flag_rotated_alert,
+                &[
+                    ("challenge", &challenge.id),
+                    ("from", &format!("<t:{}:f>", window.valid_from.timestamp())),
+                    ("until", &until),
+                    ("policy", &policy),
+                ],
+            ),
+        )
+        .await;
+    }
+    Ok(())
+}
+
+pub fn flag_rotation_loop(
+    config: Config,
+    ctx: serenity::Context,
+    state: Arc<WelcomeState>,
+    audit: Arc<AuditLog>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ROTATION_CHECK);
+        loop {
+            interval.tick().await;
+            if let Err(err) = rotate_flags(&ctx.http, &config, &state, &audit).await {
+                error!("Failed to rotate welcome flags: {:?}", err);
+            }
+        }
+    });
+}
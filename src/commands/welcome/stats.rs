@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{serde::ts_seconds, DateTime, Datelike, Duration, Utc};
+use poise::{
+    serenity_prelude::{CreateAllowedMentions, Error},
+    CreateReply,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{checks::ctfnote_admin, Context};
+
+// Academic years start in September.
+const ACADEMIC_YEAR_START_MONTH: u32 = 9;
+const STATS_DAYS: i64 = 14;
+const RECENT_SOLVERS: usize = 10;
+const LEADERBOARD_SIZE: usize = 10;
+
+/// A successful welcome challenge submission.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Solve {
+    pub user_id: u64,
+    pub challenge: String,
+    #[serde(with = "ts_seconds")]
+    pub time: DateTime<Utc>,
+    pub attempts: u32,
+    /// Hints unlocked before solving.
+    #[serde(default)]
+    pub hints: u32,
+}
+
+/// The academic year `time` falls in, e.g. `2024/25`.
+fn academic_year(time: DateTime<Utc>) -> String {
+    let start = if time.month() >= ACADEMIC_YEAR_START_MONTH {
+        time.year()
+    } else {
+        time.year() - 1
+    };
+    format!("{}/{:02}", start, (start + 1) % 100)
+}
+
+fn median(values: &mut [u32]) -> Option<f64> {
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => None,
+        len if len % 2 == 0 => Some(f64::from(values[mid - 1] + values[mid]) / 2.0),
+        _ => Some(f64::from(values[mid])),
+    }
+}
+
+/// Solves of `challenge`, or of every challenge if it's `None`, oldest first.
+async fn solves_of(ctx: Context<'_>, challenge: Option<&str>) -> Vec<Solve> {
+    ctx.data()
+        .welcome_state
+        .solves
+        .read(|solves| {
+            solves
+                .iter()
+                .filter(|solve| {
+                    challenge.map_or(true, |id| solve.challenge.eq_ignore_ascii_case(id))
+                })
+                .cloned()
+                .collect()
+        })
+        .await
+}
+
+/// Show welcome challenge solves per day, median attempts and hints, and recent solvers
+#[poise::command(slash_command, guild_only, check = "ctfnote_admin")]
+pub async fn welcome_stats(
+    ctx: Context<'_>,
+    #[description = "Only this challenge (default: all)"] challenge: Option<String>,
+) -> Result<(), Error> {
+    let solves = solves_of(ctx, challenge.as_deref()).await;
+
+    let since = (Utc::now() - Duration::days(STATS_DAYS - 1)).date_naive();
+    let mut per_day: BTreeMap<_, usize> = BTreeMap::new();
+    for solve in solves
+        .iter()
+        .filter(|solve| solve.time.date_naive() >= since)
+    {
+        *per_day.entry(solve.time.date_naive()).or_default() += 1;
+    }
+    let mut attempts: Vec<u32> = solves.iter().map(|solve| solve.attempts).collect();
+    let mut hints: Vec<u32> = solves.iter().map(|solve| solve.hints).collect();
+    let hinted = hints.iter().filter(|&&hints| hints > 0).count();
+    let solver_ids: HashSet<u64> = solves.iter().map(|solve| solve.user_id).collect();
+    let (greeted, greeted_solved) = ctx
+        .data()
+        .welcome_state
+        .greetings
+        .read(|greetings| {
+            (
+                greetings.len(),
+                greetings
+                    .keys()
+                    .filter(|id| solver_ids.contains(id))
+                    .count(),
+            )
+        })
+        .await;
+
+    let templates = &ctx.data().config.templates;
+    let mut lines = vec![
+        templates.render(&templates.stats_total, &[("count", &solves.len())]),
+        templates.render(
+            &templates.stats_median_attempts,
+            &[(
+                "median",
+                &median(&mut attempts).map_or_else(|| "-".to_string(), |median| median.to_string()),
+            )],
+        ),
+        templates.render(
+            &templates.stats_median_hints,
+            &[
+                (
+                    "median",
+                    &median(&mut hints)
+                        .map_or_else(|| "-".to_string(), |median| median.to_string()),
+                ),
+                ("hinted", &hinted),
+            ],
+        ),
+        templates.render(
+            &templates.stats_greetings,
+            &[("greeted", &greeted), ("solved", &greeted_solved)],
+        ),
+        String::new(),
+        templates.render(&templates.stats_per_day_title, &[("days", &STATS_DAYS)]),
+    ];
+    lines.extend(per_day.iter().map(|(day, count)| {
+        templates.render(
+            &templates.stats_per_day,
+            &[("day", &day.format("%Y-%m-%d")), ("count", count)],
+        )
+    }));
+    lines.push(String::new());
+    lines.push(templates.render(&templates.stats_recent_title, &[]));
+    lines.extend(solves.iter().rev().take(RECENT_SOLVERS).map(|solve| {
+        templates.render(
+            &templates.stats_recent_solve,
+            &[
+                ("time", &format!("<t:{}:f>", solve.time.timestamp())),
+                ("user", &solve.user_id),
+                ("challenge", &solve.challenge),
+                ("attempts", &solve.attempts),
+                ("hints", &solve.hints),
+            ],
+        )
+    }));
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .content(lines.join("\n")),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Show the first solvers of a welcome challenge in each academic year
+#[poise::command(slash_command, guild_only)]
+pub async fn welcome_leaderboard(
+    ctx: Context<'_>,
+    #[description = "The challenge (default: the first one)"] challenge: Option<String>,
+) -> Result<(), Error> {
+    let templates = &ctx.data().config.templates;
+    let Some(challenge) = challenge.or_else(|| {
+        ctx.data()
+            .config
+            .welcome
+            .challenges
+            .first()
+            .map(|challenge| challenge.id.clone())
+    }) else {
+        ctx.say(templates.render(&templates.no_challenges, &[]))
+            .await?;
+        return Ok(());
+    };
+
+    let mut years: BTreeMap<String, Vec<Solve>> = BTreeMap::new();
+    for solve in solves_of(ctx, Some(&challenge)).await {
+        years
+            .entry(academic_year(solve.time))
+            .or_default()
+            .push(solve);
+    }
+
+    let mut lines =
+        vec![templates.render(&templates.leaderboard_title, &[("challenge", &challenge)])];
+    for (year, solves) in years.iter().rev() {
+        lines.push(String::new());
+        lines.push(templates.render(&templates.leaderboard_year, &[("year", year)]));
+        lines.extend(
+            solves
+                .iter()
+                .take(LEADERBOARD_SIZE)
+                .enumerate()
+                .map(|(rank, solve)| {
+                    templates.render(
+                        &templates.leaderboard_entry,
+                        &[("rank", &(rank + 1)), ("user", &solve.user_id)],
+                    )
+                }),
+        );
+    }
+    if years.is_empty() {
+        lines.push(templates.render(&templates.leaderboard_empty, &[]));
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .allowed_mentions(CreateAllowedMentions::new())
+            .content(lines.join("\n")),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::{
+    fs,
+    io::{self, Cursor, Write},
+};
+
+use poise::{
+    serenity_prelude::{AutocompleteChoice, CreateAttachment, Error},
+    CreateReply,
+};
+use slug::slugify;
+use tracing::{info, warn};
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{
+    checks::ctfnote_manager,
+    ctfnote_api::{get_ctf_tasks, get_pad_markdown, get_past_ctfs, Ctf, Task},
+    Context,
+};
+
+async fn autocomplete_past_ctf(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+    get_past_ctfs(&ctx.data().config.ctfnote)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|ctf| ctf.title.to_lowercase().contains(&partial))
+        .take(25) // Discord shows at most 25 choices
+        .map(|ctf| AutocompleteChoice::new(ctf.title, ctf.id))
+        .collect()
+}
+
+fn task_filename(task: &Task) -> String {
+    format!("{}-{}.md", task.id, slugify(&task.title))
+}
+
+fn index_markdown(ctf: &Ctf, tasks: &[Task]) -> String {
+    let solved = tasks.iter().filter(|task| task.solved).count();
+    let mut lines = vec![
+        format!("# {}", ctf.title),
+        String::new(),
+        format!(
+            "{} to {}, solved {} of {} tasks.",
+            ctf.start_time.format("%Y-%m-%d"),
+            ctf.end_time.format("%Y-%m-%d"),
+            solved,
+            tasks.len()
+        ),
+        String::new(),
+        "| Task | Solved | Flag | Solvers |".to_string(),
+        "| --- | --- | --- | --- |".to_string(),
+    ];
+    lines.extend(tasks.iter().map(|task| {
+        format!(
+            "| [{}]({}) | {} | {} | {} |",
+            task.title,
+            task_filename(task),
+            if task.solved { "yes" } else { "no" },
+            if task.flag.is_empty() {
+                String::new()
+            } else {
+                format!("`{}`", task.flag)
+            },
+            task.people.join(", ")
+        )
+    }));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn task_markdown(task: &Task, pad: &str) -> String {
+    let mut markdown = format!("# {}\n\n", task.title);
+    if !task.description.is_empty() {
+        markdown.push_str(&task.description);
+        markdown.push_str("\n\n");
+    }
+    markdown.push_str(pad);
+    markdown
+}
+
+fn build_archive(ctf: &Ctf, tasks: &[Task], pads: &[String]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default();
+
+    zip.start_file("README.md", options)?;
+    zip.write_all(index_markdown(ctf, tasks).as_bytes())?;
+    for (task, pad) in tasks.iter().zip(pads) {
+        zip.start_file(task_filename(task), options)?;
+        zip.write_all(task_markdown(task, pad).as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Export the pads of a finished CTF as a Markdown writeup archive
+#[poise::command(slash_command, guild_only, check = "ctfnote_manager")]
+pub async fn ctfnote_export_writeups(
+    ctx: Context<'_>,
+    #[description = "The finished CTF"]
+    #[autocomplete = "autocomplete_past_ctf"]
+    ctf: i32,
+) -> Result<(), Error> {
+    let config = &ctx.data().config.ctfnote;
+    let templates = &ctx.data().config.templates;
+    ctx.defer().await?;
+
+    let Some(ctf) = get_past_ctfs(config)
+        .await?
+        .into_iter()
+        .find(|past_ctf| past_ctf.id == ctf)
+    else {
+        ctx.say(templates.render(&templates.unknown_past_ctf, &[]))
+            .await?;
+        return Ok(());
+    };
+
+    let tasks = get_ctf_tasks(config, ctf.id).await?;
+    let mut pads = Vec::with_capacity(tasks.len());
+    for task in &tasks {
+        pads.push(match get_pad_markdown(config, task).await {
+            Ok(pad) => pad,
+            Err(err) => {
+                warn!("Failed to fetch the pad of {}: {:?}", task.title, err);
+                templates.render(&templates.pad_export_failed, &[])
+            }
+        });
+    }
+    let archive = build_archive(&ctf, &tasks, &pads).map_err(io::Error::from)?;
+    let filename = format!("{}.zip", slugify(&ctf.title));
+
+    if let Some(writeups_path) = &config.writeups_path {
+        fs::create_dir_all(writeups_path)?;
+        fs::write(writeups_path.join(&filename), &archive)?;
+    }
+    info!(
+        "{} exported writeups for CTFNote CTF {}",
+        ctx.author().name,
+        ctf.title
+    );
+
+    ctx.send(
+        CreateReply::default()
+            .content(templates.render(
+                &templates.writeups_exported,
+                &[("ctf", &ctf.title), ("count", &tasks.len())],
+            ))
+            .attachment(CreateAttachment::bytes(archive, filename)),
+    )
+    .await?;
+    Ok(())
+}
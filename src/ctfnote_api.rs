@@ -0,0 +1,421 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use poise::serenity_prelude::{Error, UserId};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{templates::Templates, CtfnoteConfig};
+
+mod graphql;
+
+/// Where the bot reads CTFs, tasks and participants from. Account management always goes
+/// through the admin API.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CtfnoteBackend {
+    /// The `/extra/api/admin/*` endpoints of the patched CTFNote.
+    #[default]
+    AdminApi,
+    /// The standard `/graphql` endpoint, authenticated with `graphql_token`.
+    Graphql,
+}
+
+/// CTFNote user roles, ordered from least to most privileged.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CtfnoteRole {
+    #[serde(rename = "user_guest", alias = "USER_GUEST")]
+    Guest,
+    #[serde(rename = "user_member", alias = "USER_MEMBER")]
+    Member,
+    #[serde(rename = "user_manager", alias = "USER_MANAGER")]
+    Manager,
+    #[serde(rename = "user_admin", alias = "USER_ADMIN")]
+    Admin,
+}
+
+impl CtfnoteRole {
+    pub const ALL: [Self; 4] = [Self::Guest, Self::Member, Self::Manager, Self::Admin];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Guest => "guest",
+            Self::Member => "member",
+            Self::Manager => "manager",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CtfnoteUser {
+    pub username: String,
+    pub role: CtfnoteRole,
+    #[serde(alias = "discordId")]
+    pub discord_id: Option<String>,
+}
+
+impl CtfnoteUser {
+    pub fn discord_user_id(&self) -> Option<UserId> {
+        self.discord_id
+            .as_deref()
+            .and_then(|id| id.parse().ok())
+            .map(UserId::new)
+    }
+}
+
+#[derive(Serialize)]
+struct GetRoleForDiscordUserRequest {
+    discord_id: String,
+}
+
+#[derive(Deserialize)]
+struct GetRoleForDiscordUserResponse {
+    role: Option<CtfnoteRole>,
+}
+
+/// Look up the CTFNote role of the account linked to a Discord user, if any.
+pub async fn get_role(
+    config: &CtfnoteConfig,
+    discord_id: UserId,
+) -> Result<Option<CtfnoteRole>, Error> {
+    let response: GetRoleForDiscordUserResponse = admin_get(
+        config,
+        "role",
+        &GetRoleForDiscordUserRequest {
+            discord_id: discord_id.to_string(),
+        },
+    )
+    .await?;
+    Ok(response.role)
+}
+
+// Field names mirror the CTFNote schema.
+#[allow(clippy::struct_field_names)]
+#[derive(Deserialize, Debug)]
+pub struct Ctf {
+    pub id: i32,
+    pub title: String,
+    pub weight: f64,
+    pub ctf_url: Option<String>,
+    pub logo_url: Option<String>,
+    pub ctftime_url: Option<String>,
+    pub description: String,
+    #[serde(with = "ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    // secrets_id: foreign key
+}
+
+/// Fetch the CTFs on CTFNote that haven't started yet.
+pub async fn get_upcoming_ctfs(config: &CtfnoteConfig) -> Result<Vec<Ctf>, Error> {
+    match config.backend {
+        CtfnoteBackend::AdminApi => admin_get(config, "upcoming-ctf", &()).await,
+        CtfnoteBackend::Graphql => {
+            let now = Utc::now();
+            let mut ctfs = graphql::incoming_ctfs(config).await?;
+            ctfs.retain(|ctf| ctf.start_time > now);
+            Ok(ctfs)
+        }
+    }
+}
+
+/// Fetch the CTFs on CTFNote that are currently running.
+pub async fn get_current_ctfs(config: &CtfnoteConfig) -> Result<Vec<Ctf>, Error> {
+    match config.backend {
+        CtfnoteBackend::AdminApi => admin_get(config, "current-ctf", &()).await,
+        CtfnoteBackend::Graphql => {
+            let now = Utc::now();
+            let mut ctfs = graphql::incoming_ctfs(config).await?;
+            ctfs.retain(|ctf| ctf.start_time <= now && now < ctf.end_time);
+            Ok(ctfs)
+        }
+    }
+}
+
+/// Fetch the CTFs on CTFNote that have ended.
+pub async fn get_past_ctfs(config: &CtfnoteConfig) -> Result<Vec<Ctf>, Error> {
+    match config.backend {
+        CtfnoteBackend::AdminApi => admin_get(config, "past-ctf", &()).await,
+        CtfnoteBackend::Graphql => graphql::past_ctfs(config).await,
+    }
+}
+
+#[derive(Serialize)]
+struct CtfRequest {
+    ctf_id: i32,
+}
+
+/// Fetch the CTFNote users who joined a CTF.
+pub async fn get_ctf_participants(
+    config: &CtfnoteConfig,
+    ctf_id: i32,
+) -> Result<Vec<CtfnoteUser>, Error> {
+    match config.backend {
+        CtfnoteBackend::AdminApi => {
+            admin_get(config, "ctf-participants", &CtfRequest { ctf_id }).await
+        }
+        CtfnoteBackend::Graphql => graphql::ctf_participants(config, ctf_id).await,
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Task {
+    pub id: i32,
+    pub title: String,
+    pub description: String,
+    pub flag: String,
+    pub solved: bool,
+    pub pad_url: String,
+    /// Usernames of the people who worked on the task.
+    #[serde(default)]
+    pub people: Vec<String>,
+}
+
+/// Fetch the Markdown contents of a task's pad from CTFNote's HedgeDoc.
+pub async fn get_pad_markdown(config: &CtfnoteConfig, task: &Task) -> Result<String, Error> {
+    // CTFNote stores pad URLs relative to itself, e.g. `/pad/<id>`.
+    let pad_url = if task.pad_url.starts_with('/') {
+        format!("{}{}", config.ctfnote_url, task.pad_url)
+    } else {
+        task.pad_url.clone()
+    };
+    let res = reqwest::get(format!("{pad_url}/download")).await?;
+    Ok(res.error_for_status()?.text().await?)
+}
+
+/// Fetch the tasks of a CTF.
+pub async fn get_ctf_tasks(config: &CtfnoteConfig, ctf_id: i32) -> Result<Vec<Task>, Error> {
+    match config.backend {
+        CtfnoteBackend::AdminApi => admin_get(config, "ctf-tasks", &CtfRequest { ctf_id }).await,
+        CtfnoteBackend::Graphql => graphql::ctf_tasks(config, ctf_id).await,
+    }
+}
+
+#[derive(Deserialize)]
+struct GetCtfSecretsResponse {
+    credentials: Option<String>,
+}
+
+/// Fetch the credentials stored in a CTF's secrets, if any were set.
+pub async fn get_ctf_credentials(
+    config: &CtfnoteConfig,
+    ctf_id: i32,
+) -> Result<Option<String>, Error> {
+    let response: GetCtfSecretsResponse =
+        admin_get(config, "ctf-secrets", &CtfRequest { ctf_id }).await?;
+    Ok(response
+        .credentials
+        .filter(|credentials| !credentials.is_empty()))
+}
+
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 32;
+
+pub const fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+/// Check a new username against CTFNote's rules and the existing `users`, describing the
+/// problem if it can't be registered.
+pub fn check_username(
+    templates: &Templates,
+    username: &str,
+    users: &[CtfnoteUser],
+) -> Result<(), String> {
+    let length = username.chars().count();
+    if !(USERNAME_MIN_LEN..=USERNAME_MAX_LEN).contains(&length) {
+        return Err(templates.render(
+            &templates.username_length,
+            &[("min", &USERNAME_MIN_LEN), ("max", &USERNAME_MAX_LEN)],
+        ));
+    }
+    if !username.chars().all(is_username_char) {
+        return Err(templates.render(&templates.username_chars, &[]));
+    }
+    if users
+        .iter()
+        .any(|user| user.username.eq_ignore_ascii_case(username))
+    {
+        return Err(templates.render(&templates.username_taken, &[("username", &username)]));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct MessageResponse {
+    message: String,
+}
+
+/// Result of an admin API call that either succeeds or is refused with a message.
+pub struct AdminOutcome {
+    pub success: bool,
+    pub message: String,
+}
+
+async fn admin_post_outcome<B>(
+    config: &CtfnoteConfig,
+    endpoint: &str,
+    body: &B,
+) -> Result<AdminOutcome, Error>
+where
+    B: Serialize + Sync + ?Sized,
+{
+    let res = admin_post_response(config, endpoint, body).await?;
+    let success = res.status().is_success();
+    let response = res.json::<MessageResponse>().await?;
+    Ok(AdminOutcome {
+        success,
+        message: response.message,
+    })
+}
+
+#[derive(Serialize)]
+struct RegisterRequest<'a> {
+    username: &'a str,
+    discord_id: String,
+}
+
+/// Create a CTFNote account linked to a Discord user.
+pub async fn register(
+    config: &CtfnoteConfig,
+    username: &str,
+    discord_id: UserId,
+) -> Result<AdminOutcome, Error> {
+    admin_post_outcome(
+        config,
+        "register",
+        &RegisterRequest {
+            username,
+            discord_id: discord_id.to_string(),
+        },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct LinkDiscordRequest<'a> {
+    token: &'a str,
+    discord_id: String,
+}
+
+/// Link the CTFNote account owning `token` to a Discord user.
+pub async fn link_discord(
+    config: &CtfnoteConfig,
+    token: &str,
+    discord_id: UserId,
+) -> Result<AdminOutcome, Error> {
+    admin_post_outcome(
+        config,
+        "link-discord",
+        &LinkDiscordRequest {
+            token,
+            discord_id: discord_id.to_string(),
+        },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct UnlinkDiscordRequest {
+    discord_id: String,
+}
+
+/// Unlink whichever CTFNote account is linked to a Discord user.
+pub async fn unlink_discord(
+    config: &CtfnoteConfig,
+    discord_id: UserId,
+) -> Result<AdminOutcome, Error> {
+    admin_post_outcome(
+        config,
+        "unlink-discord",
+        &UnlinkDiscordRequest {
+            discord_id: discord_id.to_string(),
+        },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct RegisterGuestRequest<'a> {
+    username: &'a str,
+    discord_id: String,
+    ctf_id: i32,
+}
+
+/// Create a `user_guest` account linked to a Discord user that can only see one CTF.
+pub async fn register_guest(
+    config: &CtfnoteConfig,
+    username: &str,
+    discord_id: UserId,
+    ctf_id: i32,
+) -> Result<AdminOutcome, Error> {
+    admin_post_outcome(
+        config,
+        "register-guest",
+        &RegisterGuestRequest {
+            username,
+            discord_id: discord_id.to_string(),
+            ctf_id,
+        },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct DeleteUserRequest<'a> {
+    username: &'a str,
+}
+
+/// Delete a CTFNote account.
+pub async fn delete_user(config: &CtfnoteConfig, username: &str) -> Result<AdminOutcome, Error> {
+    admin_post_outcome(config, "delete-user", &DeleteUserRequest { username }).await
+}
+
+/// Fetch every CTFNote user, including their role and linked Discord account.
+pub async fn get_users(config: &CtfnoteConfig) -> Result<Vec<CtfnoteUser>, Error> {
+    admin_get(config, "users", &()).await
+}
+
+fn admin_url(config: &CtfnoteConfig, endpoint: &str) -> String {
+    format!("{}/extra/api/admin/{}", config.ctfnote_url, endpoint)
+}
+
+/// GET an endpoint of the CTFNote admin API, sending `query` as URL parameters.
+pub async fn admin_get<Q, R>(config: &CtfnoteConfig, endpoint: &str, query: &Q) -> Result<R, Error>
+where
+    Q: Serialize + Sync + ?Sized,
+    R: DeserializeOwned,
+{
+    let res = reqwest::Client::new()
+        .get(admin_url(config, endpoint))
+        .basic_auth("admin", Some(&config.ctfnote_admin_api_password))
+        .query(query)
+        .send()
+        .await?;
+    Ok(res.json::<R>().await?)
+}
+
+/// POST `body` as JSON to an endpoint of the CTFNote admin API.
+pub async fn admin_post<B, R>(config: &CtfnoteConfig, endpoint: &str, body: &B) -> Result<R, Error>
+where
+    B: Serialize + Sync + ?Sized,
+    R: DeserializeOwned,
+{
+    let res = admin_post_response(config, endpoint, body).await?;
+    Ok(res.json::<R>().await?)
+}
+
+async fn admin_post_response<B>(
+    config: &CtfnoteConfig,
+    endpoint: &str,
+    body: &B,
+) -> Result<reqwest::Response, Error>
+where
+    B: Serialize + Sync + ?Sized,
+{
+    Ok(reqwest::Client::new()
+        .post(admin_url(config, endpoint))
+        .basic_auth("admin", Some(&config.ctfnote_admin_api_password))
+        .json(body)
+        .send()
+        .await?)
+}
@@ -0,0 +1,259 @@
+//! Read-only access to the `/graphql` endpoint of stock CTFNote.
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::error;
+
+use super::{Ctf, CtfnoteUser, Task};
+use crate::CtfnoteConfig;
+
+#[derive(Serialize)]
+struct GraphqlRequest<'a, V> {
+    query: &'a str,
+    variables: V,
+}
+
+#[derive(Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CtfVariables {
+    ctf_id: i32,
+}
+
+async fn query<V, T>(config: &CtfnoteConfig, query: &str, variables: V) -> Result<T, Error>
+where
+    V: Serialize + Send,
+    T: DeserializeOwned,
+{
+    let token = config
+        .graphql_token
+        .as_deref()
+        .ok_or(Error::Other("ctfnote.graphql_token is not configured"))?;
+    let res = reqwest::Client::new()
+        .post(format!("{}/graphql", config.ctfnote_url))
+        .bearer_auth(token)
+        .json(&GraphqlRequest { query, variables })
+        .send()
+        .await?;
+    let response = res.json::<GraphqlResponse<T>>().await?;
+
+    match response.data {
+        Some(data) if response.errors.is_empty() => Ok(data),
+        _ => {
+            for err in response.errors {
+                error!("CTFNote GraphQL error: {}", err.message);
+            }
+            Err(Error::Other("CTFNote GraphQL query failed"))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlCtf {
+    id: i32,
+    title: String,
+    weight: f64,
+    ctf_url: Option<String>,
+    logo_url: Option<String>,
+    ctftime_url: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+impl From<GraphqlCtf> for Ctf {
+    fn from(ctf: GraphqlCtf) -> Self {
+        Self {
+            id: ctf.id,
+            title: ctf.title,
+            weight: ctf.weight,
+            ctf_url: ctf.ctf_url,
+            logo_url: ctf.logo_url,
+            ctftime_url: ctf.ctftime_url,
+            description: ctf.description.unwrap_or_default(),
+            start_time: ctf.start_time,
+            end_time: ctf.end_time,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IncomingCtfData {
+    incoming_ctf: Nodes<GraphqlCtf>,
+}
+
+/// CTFs that haven't ended yet, both upcoming and running.
+pub async fn incoming_ctfs(config: &CtfnoteConfig) -> Result<Vec<Ctf>, Error> {
+    let data: IncomingCtfData = query(
+        config,
+        "query IncomingCtfs {
+            incomingCtf {
+                nodes {
+                    id title weight ctfUrl logoUrl ctftimeUrl description startTime endTime
+                }
+            }
+        }",
+        (),
+    )
+    .await?;
+    Ok(data.incoming_ctf.nodes.into_iter().map(Ctf::from).collect())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PastCtfData {
+    past_ctf: Nodes<GraphqlCtf>,
+}
+
+pub async fn past_ctfs(config: &CtfnoteConfig) -> Result<Vec<Ctf>, Error> {
+    let data: PastCtfData = query(
+        config,
+        "query PastCtfs {
+            pastCtf {
+                nodes {
+                    id title weight ctfUrl logoUrl ctftimeUrl description startTime endTime
+                }
+            }
+        }",
+        (),
+    )
+    .await?;
+    Ok(data.past_ctf.nodes.into_iter().map(Ctf::from).collect())
+}
+
+#[derive(Deserialize)]
+struct Invitation {
+    profile: CtfnoteUser,
+}
+
+#[derive(Deserialize)]
+struct CtfInvitations {
+    invitations: Nodes<Invitation>,
+}
+
+#[derive(Deserialize)]
+struct CtfParticipantsData {
+    ctf: Option<CtfInvitations>,
+}
+
+pub async fn ctf_participants(
+    config: &CtfnoteConfig,
+    ctf_id: i32,
+) -> Result<Vec<CtfnoteUser>, Error> {
+    let data: CtfParticipantsData = query(
+        config,
+        "query CtfParticipants($ctfId: Int!) {
+            ctf(id: $ctfId) {
+                invitations {
+                    nodes { profile { username role discordId } }
+                }
+            }
+        }",
+        CtfVariables { ctf_id },
+    )
+    .await?;
+    Ok(data
+        .ctf
+        .map(|ctf| {
+            ctf.invitations
+                .nodes
+                .into_iter()
+                .map(|invitation| invitation.profile)
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct WorkOnTask {
+    profile: Profile,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlTask {
+    id: i32,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    flag: String,
+    solved: Option<bool>,
+    pad_url: String,
+    work_on_tasks: Nodes<WorkOnTask>,
+}
+
+impl From<GraphqlTask> for Task {
+    fn from(task: GraphqlTask) -> Self {
+        Self {
+            id: task.id,
+            title: task.title,
+            description: task.description.unwrap_or_default(),
+            flag: task.flag,
+            solved: task.solved.unwrap_or(false),
+            pad_url: task.pad_url,
+            people: task
+                .work_on_tasks
+                .nodes
+                .into_iter()
+                .map(|work| work.profile.username)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CtfTasks {
+    tasks: Nodes<GraphqlTask>,
+}
+
+#[derive(Deserialize)]
+struct CtfTasksData {
+    ctf: Option<CtfTasks>,
+}
+
+pub async fn ctf_tasks(config: &CtfnoteConfig, ctf_id: i32) -> Result<Vec<Task>, Error> {
+    let data: CtfTasksData = query(
+        config,
+        "query CtfTasks($ctfId: Int!) {
+            ctf(id: $ctfId) {
+                tasks {
+                    nodes {
+                        id title description flag solved padUrl
+                        workOnTasks { nodes { profile { username } } }
+                    }
+                }
+            }
+        }",
+        CtfVariables { ctf_id },
+    )
+    .await?;
+    Ok(data
+        .ctf
+        .map(|ctf| ctf.tasks.nodes.into_iter().map(Task::from).collect())
+        .unwrap_or_default())
+}
@@ -2,31 +2,64 @@
 #![warn(clippy::pedantic)]
 #![warn(clippy::nursery)]
 #![allow(clippy::no_effect_underscore_binding)]
+// Doc comments on commands double as their Discord descriptions.
+#![allow(clippy::doc_markdown)]
 
+mod audit;
+mod checks;
 mod commands;
+mod ctfnote_api;
+mod storage;
+mod templates;
+mod webhook;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use commands::{
-    ctfnote::{ctfnote_create_account, ctfnote_link, ctfnote_login, ctfnote_announce_upcoming},
+    audit::audit,
+    credentials::{credentials_loop, ctf_credentials, CredentialsDelivery},
+    ctfnote::{
+        ctfnote_announce_loop, ctfnote_announce_upcoming, ctfnote_create_account, ctfnote_create_ctf, ctfnote_link,
+        ctfnote_login, ctfnote_reset_password, CachedToken, ctfnote_unlink, ctfnote_whoami, handle_join_button,
+    },
     ctftime::{generate_embed, get_upcoming_ctf, Ctf, TimeFrame},
+    guest::{ctfnote_invite_guest, guest_expiry_loop, GuestAccount},
+    provision::ctfnote_provision_role,
     register_commands::register_slash_commands,
+    role_sync::{ctfnote_sync_roles, role_sync_loop},
+    task::{task, CachedTaskTitles},
     welcome,
+    writeups::ctfnote_export_writeups,
 };
 use poise::{
     serenity_prelude::{
-        self as serenity, futures::lock::Mutex, CacheHttp, ChannelId, ClientBuilder, CreateAllowedMentions, Error, GuildId
-    }, Framework, PrefixFrameworkOptions
+        self as serenity, futures::lock::Mutex, CacheHttp, ChannelId, ClientBuilder, CreateAllowedMentions, Error, GuildId, UserId
+    }, Framework, FrameworkContext, PrefixFrameworkOptions
 };
 use serde::Deserialize;
 use serenity::builder::CreateMessage;
-use std::{collections::HashSet, fs::read_to_string, sync::Arc};
+use std::{collections::{HashMap, HashSet}, fs::read_to_string, net::SocketAddr, path::PathBuf, sync::Arc};
 use tracing::{error, info, log::warn};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
+use crate::audit::AuditLog;
+use crate::checks::CachedRole;
+use crate::ctfnote_api::CtfnoteBackend;
+use crate::storage::JsonStore;
+use crate::templates::Templates;
+use crate::webhook::webhook_server;
 use crate::commands::ctftime::assign_ctf_announcement_role;
-use crate::welcome::welcome;
+use crate::welcome::{
+    challenges, flag, welcome,
+    flag_hash::hash_flag_command,
+    greeting::greet_member,
+    hints::hint,
+    rotation::{flag_rotation_loop, RotationPolicy},
+    stats::{welcome_leaderboard, welcome_stats},
+    WelcomeState,
+};
 
 type Context<'a> = poise::Context<'a, Data, Error>;
+type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, Error>;
 
 #[derive(Eq, Hash, PartialEq)]
 pub struct CTFLog {
@@ -63,31 +96,215 @@ pub(crate) struct Config {
     ctftime_loop_seconds: u64,
     ctfnote: CtfnoteConfig,
     team_channel_id: u64,
+    #[serde(default = "default_data_dir")]
+    data_dir: PathBuf,
+    webhook: Option<WebhookConfig>,
+    audit_channel_id: Option<u64>,
+    #[serde(default)]
+    templates: Templates,
+    templates_file: Option<PathBuf>,
+}
+
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("data")
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct WebhookConfig {
+    bind: SocketAddr,
+    secret: String,
+    channel_id: u64,
+    task_channel_id: Option<u64>,
+    ctf_channel_id: Option<u64>,
+    user_channel_id: Option<u64>,
 }
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct WelcomeConfig {
-    flag: String,
+    challenges: Vec<ChallengeConfig>,
+    alert_channel_id: Option<u64>,
+    greeting: Option<String>,
+    welcome_channel_id: Option<u64>,
+    /// Read from `WELCOME_FLAG_SECRET` rather than config.toml, which is copied into the image.
+    #[serde(skip)]
+    flag_secret: Option<String>,
+    #[serde(default = "default_flag_prefix")]
+    flag_prefix: String,
+    #[serde(default)]
+    rate_limit: RateLimitConfig,
+}
+
+fn default_flag_prefix() -> String {
+    "sigint".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct RateLimitConfig {
+    free_attempts: u32,
+    cooldown_seconds: u32,
+    max_cooldown_seconds: u32,
+    global_attempts_per_minute: usize,
+    alert_threshold: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            free_attempts: 5,
+            cooldown_seconds: 30,
+            max_cooldown_seconds: 3600,
+            global_attempts_per_minute: 60,
+            alert_threshold: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct ChallengeConfig {
+    id: String,
+    name: String,
+    description: String,
+    flag_hash: Option<String>,
+    #[serde(default)]
+    flags: Vec<FlagWindow>,
+    #[serde(default)]
+    on_rotation: RotationPolicy,
+    veteran_role_id: Option<u64>,
+    #[serde(default)]
+    dynamic_flag: bool,
     role_id: u64,
+    requires: Option<String>,
+    #[serde(default)]
+    hints: Vec<HintConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct HintConfig {
+    text: String,
+    after_attempts: Option<u32>,
+    after_minutes: Option<u32>,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct FlagWindow {
+    flag_hash: String,
+    valid_from: DateTime<Utc>,
+    valid_until: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct CtfnoteConfig {
     ctfnote_url: String,
     ctfnote_admin_api_password: String,
+    #[serde(default)]
+    backend: CtfnoteBackend,
+    graphql_token: Option<String>,
+    jwt: Option<JwtConfig>,
+    writeups_path: Option<PathBuf>,
+    role_sync: Option<RoleSyncConfig>,
+    announce: Option<AnnounceConfig>,
+    credentials: Option<CredentialsConfig>,
+    role_cache_seconds: Option<u64>,
+    #[serde(default)]
+    override_role_ids: Vec<u64>,
+}
+
+pub(crate) const DEFAULT_JWT_AUDIENCE: &str = "postgraphile";
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct JwtConfig {
+    secret: Option<String>,
+    public_key: Option<String>,
+    #[serde(default = "default_jwt_audience")]
+    audience: String,
+}
+
+fn default_jwt_audience() -> String {
+    DEFAULT_JWT_AUDIENCE.to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct RoleSyncConfig {
+    loop_seconds: Option<u64>,
+    guest_role_id: Option<u64>,
+    member_role_id: Option<u64>,
+    manager_role_id: Option<u64>,
+    admin_role_id: Option<u64>,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct AnnounceConfig {
+    channel_id: u64,
+    loop_seconds: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct CredentialsConfig {
+    loop_seconds: u64,
+    delivery: CredentialsDelivery,
 }
 
 // Custom user data passed to all command functions
 pub struct Data {
     config: Config,
+    role_cache: Mutex<HashMap<UserId, CachedRole>>,
+    token_cache: Mutex<HashMap<UserId, CachedToken>>,
+    task_titles: Mutex<Option<CachedTaskTitles>>,
+    announced_ctfs: Arc<JsonStore<HashSet<i32>>>,
+    guest_accounts: Arc<JsonStore<Vec<GuestAccount>>>,
+    audit: Arc<AuditLog>,
+    welcome_state: Arc<WelcomeState>,
+}
+
+fn commands() -> Vec<poise::Command<Data, Error>> {
+    vec![
+        welcome(),
+        flag(),
+        challenges(),
+        hint(),
+        welcome_stats(),
+        welcome_leaderboard(),
+        register_slash_commands(),
+        get_upcoming_ctf(),
+        assign_ctf_announcement_role(),
+        ctfnote_link(),
+        ctfnote_login(),
+        ctfnote_whoami(),
+        ctfnote_unlink(),
+        ctfnote_reset_password(),
+        ctfnote_create_account(),
+        ctfnote_announce_upcoming(),
+        ctfnote_create_ctf(),
+        ctfnote_provision_role(),
+        ctfnote_invite_guest(),
+        ctfnote_export_writeups(),
+        task(),
+        ctf_credentials(),
+        ctfnote_sync_roles(),
+        audit(),
+    ]
 }
 
 #[tokio::main]
 async fn main() {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("hash-flag") {
+        hash_flag_command(args.next());
+        return;
+    }
+
     // Load configurations.
-    let config: Config =
+    let mut config: Config =
         toml::from_str(&read_to_string("config.toml").expect("Error accessing config.toml"))
             .expect("Error parsing config.toml");
+    if let Some(templates_file) = &config.templates_file {
+        config.templates = toml::from_str(
+            &read_to_string(templates_file).expect("Error accessing the templates file"),
+        )
+        .expect("Error parsing the templates file");
+    }
+    config.welcome.flag_secret = std::env::var("WELCOME_FLAG_SECRET").ok();
 
     // Initialize the logger to use environment variables.
     let subscriber = FmtSubscriber::builder()
@@ -96,6 +313,23 @@ async fn main() {
 
     tracing::subscriber::set_global_default(subscriber).expect("Failed to start the logger");
 
+    let announced_ctfs = Arc::new(
+        JsonStore::open(&config.data_dir, "announced_ctfs.json")
+            .expect("Error loading announced CTFs"),
+    );
+    let delivered_credentials = Arc::new(
+        JsonStore::open(&config.data_dir, "delivered_credentials.json")
+            .expect("Error loading delivered CTF credentials"),
+    );
+    let guest_accounts = Arc::new(
+        JsonStore::open(&config.data_dir, "guest_accounts.json")
+            .expect("Error loading CTFNote guest accounts"),
+    );
+    let audit_log = Arc::new(AuditLog::open(&config).expect("Error loading audit log"));
+    let welcome_state = Arc::new(
+        WelcomeState::open(&config.data_dir).expect("Error loading welcome challenge state"),
+    );
+
     let config_clone = config.clone();
     let config_clone_2 = config.clone();
     let guild_id = config.guild_id;
@@ -104,16 +338,7 @@ async fn main() {
     let framework = Framework::builder()
         .options(poise::FrameworkOptions {
             // TODO: Add allowed mentions
-            commands: vec![
-                welcome(),
-                register_slash_commands(),
-                get_upcoming_ctf(),
-                assign_ctf_announcement_role(),
-                ctfnote_link(),
-                ctfnote_login(),
-                ctfnote_create_account(),
-                ctfnote_announce_upcoming(),
-            ],
+            commands: commands(),
             prefix_options: PrefixFrameworkOptions {
                 prefix: Some("!".to_string()),
                 additional_prefixes: Vec::new(),
@@ -122,22 +347,39 @@ async fn main() {
                 case_insensitive_commands: true,
                 ..Default::default()
             },
+            event_handler: |ctx, event, framework, data| {
+                Box::pin(event_handler(ctx, event, framework, data))
+            },
             ..Default::default()
         })
         .setup(move |ctx, _ready, _framework| {
             Box::pin(async move {
                 poise::builtins::register_in_guild(ctx.http(), &_framework.options().commands, GuildId::new(guild_id)).await?;
-                post_ctf_loop(config_clone, ctx.clone());
+                post_ctf_loop(config_clone.clone(), ctx.clone());
+                role_sync_loop(config_clone.clone(), ctx.clone(), audit_log.clone());
+                ctfnote_announce_loop(config_clone.clone(), ctx.clone(), announced_ctfs.clone(), audit_log.clone());
+                webhook_server(config_clone.clone(), ctx.clone(), announced_ctfs.clone());
+                flag_rotation_loop(config_clone.clone(), ctx.clone(), welcome_state.clone(), audit_log.clone());
+                guest_expiry_loop(config_clone.clone(), ctx.clone(), guest_accounts.clone(), audit_log.clone());
+                credentials_loop(config_clone, ctx.clone(), delivered_credentials);
                 Ok(Data {
                     config: config_clone_2,
+                    role_cache: Mutex::new(HashMap::new()),
+                    token_cache: Mutex::new(HashMap::new()),
+                    task_titles: Mutex::new(None),
+                    announced_ctfs,
+                    guest_accounts,
+                    audit: audit_log,
+                    welcome_state,
                 })
             })
         })
         .build();
 
     let token = &config.discord_token;
-    let intents =
-        serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT;
+    let intents = serenity::GatewayIntents::non_privileged()
+        | serenity::GatewayIntents::MESSAGE_CONTENT
+        | serenity::GatewayIntents::GUILD_MEMBERS;
     let client = ClientBuilder::new(token, intents)
         .framework(framework)
         .await;
@@ -147,6 +389,24 @@ async fn main() {
     }
 }
 
+async fn event_handler(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    _framework: FrameworkContext<'_, Data, Error>,
+    data: &Data,
+) -> Result<(), Error> {
+    match event {
+        serenity::FullEvent::InteractionCreate {
+            interaction: serenity::Interaction::Component(mci),
+        } => handle_join_button(ctx, data, mci).await?,
+        serenity::FullEvent::GuildMemberAddition { new_member } => {
+            greet_member(ctx, data, new_member).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
 fn post_ctf_loop(config: Config, ctx: poise::serenity_prelude::Context) {
     // Loop to update us with upcoming ctfs. Also keeps a log of all previously displayed CTFS to make sure we don't display them multiple times.
     // Clear all ctfs in the past to stop memory leaks. This state is used to make sure we don't show multiple ctfs
@@ -223,7 +483,7 @@ fn post_ctf_loop(config: Config, ctx: poise::serenity_prelude::Context) {
                         .id()
                         .send_message(
                             ctx.http.clone(),
-                            CreateMessage::new().add_embed(generate_embed(ctf)),
+                            CreateMessage::new().add_embed(generate_embed(ctf, &config.templates)),
                         )
                         .await
                         .unwrap();
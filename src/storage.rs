@@ -0,0 +1,55 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use poise::serenity_prelude::futures::lock::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A value persisted as a JSON file, rewritten in full on every update.
+pub struct JsonStore<T> {
+    path: PathBuf,
+    value: Mutex<T>,
+    /// Whether the file didn't exist yet when the store was opened.
+    created: bool,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> JsonStore<T> {
+    /// Load the store from `name` inside `data_dir`, starting from the default value if the file
+    /// doesn't exist yet.
+    pub fn open(data_dir: &Path, name: &str) -> io::Result<Self> {
+        fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(name);
+        let (value, created) = match fs::read_to_string(&path) {
+            Ok(contents) => (serde_json::from_str(&contents)?, false),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (T::default(), true),
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            path,
+            value: Mutex::new(value),
+            created,
+        })
+    }
+
+    /// Whether the store started from the default value because its file didn't exist yet.
+    pub const fn created(&self) -> bool {
+        self.created
+    }
+
+    pub async fn read<R>(&self, f: impl FnOnce(&T) -> R + Send) -> R {
+        f(&*self.value.lock().await)
+    }
+
+    /// Apply `f` to the stored value and write the result back to disk.
+    pub async fn update<R>(&self, f: impl FnOnce(&mut T) -> R + Send) -> io::Result<R> {
+        let mut value = self.value.lock().await;
+        let result = f(&mut value);
+
+        // Write to a temporary file first so a crash never leaves a truncated store behind.
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&*value)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(result)
+    }
+}
@@ -0,0 +1,394 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+/// User-facing wording, overridable from `[templates]` in config.toml or a separate
+/// `templates_file`. Every template can use `{community}` and `{invite}`; the other placeholders
+/// are listed next to each template.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Templates {
+    pub community_name: String,
+    pub invite_url: String,
+
+    /// `{challenge}`
+    pub unknown_challenge: String,
+    pub join_server: String,
+    pub contact_admin: String,
+    /// `{role}`
+    pub already_solved: String,
+    /// `{required}`, `{challenge}`
+    pub requires_challenge: String,
+    pub too_many_flags: String,
+    /// `{time}`
+    pub flag_cooldown: String,
+    /// `{retry}`, empty or `retry_after`
+    pub wrong_flag: String,
+    /// `{time}`
+    pub retry_after: String,
+    pub shared_flag: String,
+    /// `{role}`, `{user}`
+    pub flag_solved: String,
+    pub challenges_intro: String,
+    /// `{prefix}`, the welcome `flag_prefix`
+    pub flag_placeholder: String,
+    /// `{status}`, `{challenge}`, `{role}`, `{description}`
+    pub challenge_line: String,
+    pub challenge_solved: String,
+    /// `{required}`
+    pub challenge_locked: String,
+    pub challenge_open: String,
+    /// `{challenge}`
+    pub no_hints: String,
+    /// `{challenge}`
+    pub hints_title: String,
+    /// `{number}`, `{hint}`
+    pub hint_unlocked: String,
+    /// `{number}`, `{requirement}`, one or more of the hint requirements below
+    pub hint_locked: String,
+    /// `{count}`
+    pub hint_after_attempts: String,
+    /// `{time}`
+    pub hint_at: String,
+    /// `{minutes}`
+    pub hint_after_minutes: String,
+    pub hint_requirement_separator: String,
+    /// `{user}`, `{name}`, `{count}`, `{challenge}`
+    pub brute_force_alert: String,
+    /// `{user}`, `{name}`, `{challenge}`, `{owner}`
+    pub shared_flag_alert: String,
+    /// `{challenge}`
+    pub flag_lapsed_alert: String,
+    /// `{challenge}`, `{from}`, `{until}`, empty or `flag_rotated_until`, `{policy}`, empty,
+    /// `flag_rotated_revoked` or `flag_rotated_veteran`
+    pub flag_rotated_alert: String,
+    /// `{time}`
+    pub flag_rotated_until: String,
+    /// `{count}`
+    pub flag_rotated_revoked: String,
+    /// `{count}`
+    pub flag_rotated_veteran: String,
+
+    /// `{count}`
+    pub stats_total: String,
+    /// `{median}`
+    pub stats_median_attempts: String,
+    /// `{median}`, `{hinted}`
+    pub stats_median_hints: String,
+    /// `{greeted}`, `{solved}`
+    pub stats_greetings: String,
+    /// `{days}`
+    pub stats_per_day_title: String,
+    /// `{day}`, `{count}`
+    pub stats_per_day: String,
+    pub stats_recent_title: String,
+    /// `{time}`, `{user}`, `{challenge}`, `{attempts}`, `{hints}`
+    pub stats_recent_solve: String,
+    pub no_challenges: String,
+    /// `{challenge}`
+    pub leaderboard_title: String,
+    /// `{year}`
+    pub leaderboard_year: String,
+    /// `{rank}`, `{user}`
+    pub leaderboard_entry: String,
+    pub leaderboard_empty: String,
+
+    /// `{ctf}`
+    pub embed_title: String,
+    pub embed_dates: String,
+    /// `{start}`, `{end}`
+    pub embed_dates_value: String,
+    pub embed_ctf_page: String,
+    pub embed_weight: String,
+    pub embed_participants: String,
+    pub embed_format: String,
+    pub join_button: String,
+    pub view_button: String,
+    pub no_upcoming_ctftime: String,
+    pub no_upcoming_ctfnote: String,
+    pub announcement_role_toggled: String,
+
+    pub invalid_login_token: String,
+    /// `{url}`, `{role}`, `{time}`
+    pub login_link: String,
+    /// `{username}`, `{role}`, `{expiry}`
+    pub linked_account: String,
+    /// `{time}`
+    pub login_token_expiry: String,
+    pub no_login_token: String,
+    /// `{url}`
+    pub password_reset_link: String,
+    pub password_reset_sent: String,
+    pub dm_failed: String,
+    /// `{channel}`
+    pub team_channel_only: String,
+    pub try_again_button: String,
+    /// `{min}`, `{max}`
+    pub username_length: String,
+    pub username_chars: String,
+    /// `{username}`
+    pub username_taken: String,
+    pub invalid_ctf_times: String,
+    pub ctf_ends_before_start: String,
+    /// `{role}`
+    pub current_ctfnote_role: String,
+    pub not_linked: String,
+    /// `{role}`, `{reason}`, `not_linked` or `current_ctfnote_role`
+    pub ctfnote_role_required: String,
+
+    pub task_thread_only: String,
+    /// `{task}`, `{solvers}`, `{flag}`
+    pub task_solved: String,
+    /// `{ctf}`, `{credentials}`
+    pub ctf_credentials: String,
+    pub no_credentials: String,
+    pub no_joined_ctf: String,
+    pub unknown_past_ctf: String,
+    pub pad_export_failed: String,
+    /// `{ctf}`, `{count}`
+    pub writeups_exported: String,
+
+    /// `{user}`, `{username}`
+    pub guest_already_linked: String,
+    /// `{username}`, `{url}`, `{expiry}`, `{details}`
+    pub guest_account_dm: String,
+    pub guest_dm_sent: String,
+    pub guest_dm_failed: String,
+    /// `{username}`, `{user}`, `{expiry}`, `{delivery}`, `guest_dm_sent` or `guest_dm_failed`
+    pub guest_created: String,
+
+    pub role_sync_disabled: String,
+    pub roles_in_sync: String,
+    /// `{count}`
+    pub role_sync_dry_run: String,
+    /// `{count}`
+    pub role_sync_done: String,
+    /// `{role}`, `{user}`
+    pub role_sync_add: String,
+    /// `{role}`, `{user}`
+    pub role_sync_remove: String,
+    /// `{count}`
+    pub provision_created: String,
+    /// `{count}`
+    pub provision_skipped: String,
+    /// `{count}`
+    pub provision_failed: String,
+    /// `{count}`
+    pub and_more: String,
+    pub no_audit_entries: String,
+    /// `{count}`
+    pub older_audit_entries: String,
+
+    /// `{task}`, `{ctf}`
+    pub task_created_event: String,
+    /// `{task}`, `{ctf}`, `{solvers}`, empty or `task_solved_by`
+    pub task_solved_event: String,
+    /// `{solvers}`
+    pub task_solved_by: String,
+    /// `{username}`, `{discord}`, empty or `user_registered_discord`
+    pub user_registered_event: String,
+    /// `{user}`
+    pub user_registered_discord: String,
+}
+
+impl Default for Templates {
+    #[allow(clippy::too_many_lines)]
+    fn default() -> Self {
+        Self {
+            community_name: "SIGINT".to_string(),
+            invite_url: "https://discord.gg/WynY7FD3HP".to_string(),
+
+            unknown_challenge: "There is no challenge called `{challenge}`, use `/challenges` in the {community} server to see them all.".to_string(),
+            join_server: "Please join the {community} server first! {invite}".to_string(),
+            contact_admin: "An error has occurred, please contact {community} admin".to_string(),
+            already_solved: "You already have the \"{role}\" role.".to_string(),
+            requires_challenge: "You need to solve `{required}` before attempting `{challenge}`."
+                .to_string(),
+            too_many_flags: "I'm receiving too many flags right now, please try again in a minute."
+                .to_string(),
+            flag_cooldown: "Slow down! You can try again {time}.".to_string(),
+            wrong_flag: "I don't think that is the right flag... Try harder!{retry}".to_string(),
+            retry_after: " You can try again {time}.".to_string(),
+            shared_flag: "That flag was made for someone else, solve the challenge yourself!"
+                .to_string(),
+            flag_solved: "Congratulations! You have earned the \"{role}\" role!".to_string(),
+            challenges_intro:
+                "Use `/welcome <challenge>` or DM me `!flag <challenge> <flag>` to submit a flag."
+                    .to_string(),
+            flag_placeholder: "{prefix}{...}".to_string(),
+            challenge_line: "{status} **{challenge}**, \"{role}\" role: {description}".to_string(),
+            challenge_solved: "✅".to_string(),
+            challenge_locked: "🔒 (solve `{required}` first)".to_string(),
+            challenge_open: "⬜".to_string(),
+            no_hints: "`{challenge}` has no hints, good luck!".to_string(),
+            hints_title: "**Hints for `{challenge}`**".to_string(),
+            hint_unlocked: "{number}. {hint}".to_string(),
+            hint_locked: "{number}. 🔒 unlocks {requirement}".to_string(),
+            hint_after_attempts: "after {count} more wrong flags".to_string(),
+            hint_at: "{time}".to_string(),
+            hint_after_minutes: "{minutes} minutes after your first wrong flag".to_string(),
+            hint_requirement_separator: " or ".to_string(),
+            brute_force_alert:
+                "⚠️ <@{user}> ({name}) has submitted {count} wrong flags for the `{challenge}` challenge."
+                    .to_string(),
+            shared_flag_alert:
+                "⚠️ <@{user}> ({name}) submitted the personal `{challenge}` flag of <@{owner}>."
+                    .to_string(),
+            flag_lapsed_alert:
+                "⚠️ The `{challenge}` challenge has no valid flag anymore, add a new one to config.toml."
+                    .to_string(),
+            flag_rotated_alert:
+                "🔄 The `{challenge}` challenge now accepts the flag valid from {from}{until}.{policy}"
+                    .to_string(),
+            flag_rotated_until: " until {time}".to_string(),
+            flag_rotated_revoked: " Removed the role from {count} members.".to_string(),
+            flag_rotated_veteran: " Moved {count} members to the veteran role.".to_string(),
+
+            stats_total: "**{count} solves in total**".to_string(),
+            stats_median_attempts: "Median attempts: {median}".to_string(),
+            stats_median_hints: "Median hints: {median}, {hinted} of the solvers used hints"
+                .to_string(),
+            stats_greetings: "Greeted new members: {greeted}, of whom {solved} solved it"
+                .to_string(),
+            stats_per_day_title: "**Solves in the last {days} days**".to_string(),
+            stats_per_day: "{day}: {count}".to_string(),
+            stats_recent_title: "**Recent solvers**".to_string(),
+            stats_recent_solve:
+                "{time} <@{user}> solved `{challenge}` in {attempts} attempts with {hints} hints"
+                    .to_string(),
+            no_challenges: "There are no welcome challenges.".to_string(),
+            leaderboard_title: "**First solvers of `{challenge}`**".to_string(),
+            leaderboard_year: "**{year}**".to_string(),
+            leaderboard_entry: "{rank}. <@{user}>".to_string(),
+            leaderboard_empty: "Nobody has solved it yet!".to_string(),
+
+            embed_title: "{ctf}".to_string(),
+            embed_dates: "Dates".to_string(),
+            embed_dates_value: "Starts: {start}.\n Ends: {end}".to_string(),
+            embed_ctf_page: "CTF Page".to_string(),
+            embed_weight: "Weight".to_string(),
+            embed_participants: "Participants".to_string(),
+            embed_format: "Format".to_string(),
+            join_button: "Join on CTFNote".to_string(),
+            view_button: "View on CTFNote".to_string(),
+            no_upcoming_ctftime: "No Upcoming CTFs in that time period".to_string(),
+            no_upcoming_ctfnote: "No upcoming CTFs on CTFNote.".to_string(),
+            announcement_role_toggled: "Success".to_string(),
+
+            invalid_login_token: "CTFNote gave me an invalid or expired login token, please contact {community} admin".to_string(),
+            login_link: "<{url}>\nLogs you in as a CTFNote {role}, expires {time}".to_string(),
+            linked_account: "You are linked to CTFNote user **{username}** ({role}), {expiry}."
+                .to_string(),
+            login_token_expiry: "login token expires {time}".to_string(),
+            no_login_token: "no active login token".to_string(),
+            password_reset_link:
+                "Reset your CTFNote password here (the link only works once):\n<{url}>".to_string(),
+            password_reset_sent: "Sent you a password reset link by DM.".to_string(),
+            dm_failed:
+                "I couldn't DM you, please allow direct messages from server members and try again."
+                    .to_string(),
+            team_channel_only: "You can only run this command in team channel {channel}"
+                .to_string(),
+            try_again_button: "Try again".to_string(),
+            username_length: "Usernames must be between {min} and {max} characters long."
+                .to_string(),
+            username_chars: "Usernames may only contain letters, digits, `_`, `.` and `-`."
+                .to_string(),
+            username_taken: "The username `{username}` is already taken.".to_string(),
+            invalid_ctf_times: "Times must look like `2024-10-31 18:00` (in UTC).".to_string(),
+            ctf_ends_before_start: "The CTF must end after it starts.".to_string(),
+            current_ctfnote_role: "you are a CTFNote {role}".to_string(),
+            not_linked: "your Discord account is not linked to CTFNote".to_string(),
+            ctfnote_role_required:
+                "You need to be a CTFNote {role} or above to use this command, but {reason}."
+                    .to_string(),
+
+            task_thread_only: "Run this command in a task thread or give the task name."
+                .to_string(),
+            task_solved: "🎉 **{task}** has been solved by {solvers}!\nFlag: {flag}".to_string(),
+            ctf_credentials: "🔑 Credentials for **{ctf}**:\n{credentials}".to_string(),
+            no_credentials: "No credentials have been set yet.".to_string(),
+            no_joined_ctf: "You haven't joined any running CTF on CTFNote.".to_string(),
+            unknown_past_ctf: "That CTF doesn't exist on CTFNote or hasn't ended yet.".to_string(),
+            pad_export_failed: "*The pad could not be exported.*".to_string(),
+            writeups_exported: "Writeups for **{ctf}** ({count} tasks)".to_string(),
+
+            guest_already_linked: "<@{user}> already has the CTFNote account `{username}`."
+                .to_string(),
+            guest_account_dm: "You've been given the CTFNote guest account `{username}` on <{url}>, it will be deleted {expiry}.\n{details}".to_string(),
+            guest_dm_sent: "The account details were sent to them by DM.".to_string(),
+            guest_dm_failed: "I couldn't DM them, ask them to run `/ctfnote_reset_password` to set a password.".to_string(),
+            guest_created:
+                "Created the guest account `{username}` for <@{user}>, it expires {expiry}. {delivery}"
+                    .to_string(),
+
+            role_sync_disabled: "CTFNote role sync is not configured.".to_string(),
+            roles_in_sync: "All linked members already have the right roles.".to_string(),
+            role_sync_dry_run: "Dry run, {count} role changes would be made:".to_string(),
+            role_sync_done: "Made {count} role changes:".to_string(),
+            role_sync_add: "+ Add <@&{role}> to <@{user}>".to_string(),
+            role_sync_remove: "- Remove <@&{role}> from <@{user}>".to_string(),
+            provision_created: "**Created: {count}**".to_string(),
+            provision_skipped: "**Skipped (already linked): {count}**".to_string(),
+            provision_failed: "**Failed: {count}**".to_string(),
+            and_more: "...and {count} more".to_string(),
+            no_audit_entries: "No matching audit entries.".to_string(),
+            older_audit_entries: "...and {count} older entries".to_string(),
+
+            task_created_event: "🆕 New task **{task}** in {ctf}".to_string(),
+            task_solved_event: "🎉 **{task}** in {ctf} has been solved{solvers}!".to_string(),
+            task_solved_by: " by {solvers}".to_string(),
+            user_registered_event: "👋 **{username}**{discord} registered on CTFNote".to_string(),
+            user_registered_discord: " (<@{user}>)".to_string(),
+        }
+    }
+}
+
+impl Templates {
+    /// Fill in `template`, replacing each `{name}` in `values` as well as `{community}` and
+    /// `{invite}`. Substituted values are not scanned again, and unknown placeholders are kept
+    /// as they are.
+    pub fn render(&self, template: &str, values: &[(&str, &(dyn Display + Sync))]) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            let Some(name) = rest.find('}').map(|end| &rest[..end]) else {
+                rendered.push('{');
+                continue;
+            };
+            let value = match name {
+                "community" => Some(self.community_name.clone()),
+                "invite" => Some(self.invite_url.clone()),
+                _ => values
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string()),
+            };
+            if let Some(value) = value {
+                rendered.push_str(&value);
+                rest = &rest[name.len() + 1..];
+            } else {
+                rendered.push('{');
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_substitutes_in_one_pass() {
+        let templates = Templates::default();
+        let rendered = templates.render(
+            "{username} in {community}, {expiry} {unknown} {",
+            &[("username", &"{expiry}"), ("expiry", &"soon")],
+        );
+        assert_eq!(rendered, "{expiry} in SIGINT, soon {unknown} {");
+    }
+}
@@ -0,0 +1,303 @@
+use std::{collections::HashSet, convert::Infallible, sync::Arc};
+
+use hyper::{
+    body::to_bytes,
+    header::AUTHORIZATION,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, CreateAllowedMentions, CreateMessage, Error, Http,
+};
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use tracing::{error, info};
+
+use crate::{
+    commands::ctfnote::ctf_announcement, ctfnote_api::Ctf, storage::JsonStore, Config,
+    WebhookConfig,
+};
+
+/// Events CTFNote POSTs to the webhook as JSON, e.g. `{"event": "task_created", ...}`.
+#[derive(Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum CtfnoteEvent {
+    TaskCreated {
+        ctf: String,
+        task: String,
+    },
+    TaskSolved {
+        ctf: String,
+        task: String,
+        #[serde(default)]
+        solvers: Vec<String>,
+    },
+    CtfCreated {
+        ctf: Ctf,
+    },
+    UserRegistered {
+        username: String,
+        discord_id: Option<String>,
+    },
+}
+
+struct WebhookState {
+    config: Config,
+    webhook: WebhookConfig,
+    http: Arc<Http>,
+    announced_ctfs: Arc<JsonStore<HashSet<i32>>>,
+}
+
+impl WebhookConfig {
+    fn channel_for(&self, event: &CtfnoteEvent) -> ChannelId {
+        let channel_id = match event {
+            CtfnoteEvent::TaskCreated { .. } | CtfnoteEvent::TaskSolved { .. } => {
+                self.task_channel_id
+            }
+            CtfnoteEvent::CtfCreated { .. } => self.ctf_channel_id,
+            CtfnoteEvent::UserRegistered { .. } => self.user_channel_id,
+        };
+        ChannelId::new(channel_id.unwrap_or(self.channel_id))
+    }
+}
+
+/// Check the request is an authenticated POST to `/webhook` and parse the event it carries.
+async fn parse_event(
+    webhook: &WebhookConfig,
+    request: Request<Body>,
+) -> Result<CtfnoteEvent, StatusCode> {
+    if request.uri().path() != "/webhook" {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if request.method() != Method::POST {
+        return Err(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let expected = format!("Bearer {}", webhook.secret);
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .is_some_and(|header| bool::from(header.as_bytes().ct_eq(expected.as_bytes())));
+    if !authorized {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let body = to_bytes(request.into_body())
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    serde_json::from_slice(&body).map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+async fn post_event(state: &WebhookState, event: CtfnoteEvent) -> Result<(), Error> {
+    let channel_id = state.webhook.channel_for(&event);
+    // Names come from CTFNote users, don't let them ping anyone.
+    let message = CreateMessage::new().allowed_mentions(CreateAllowedMentions::new());
+    let templates = &state.config.templates;
+    let message = match event {
+        CtfnoteEvent::TaskCreated { ctf, task } => message.content(templates.render(
+            &templates.task_created_event,
+            &[("task", &task), ("ctf", &ctf)],
+        )),
+        CtfnoteEvent::TaskSolved { ctf, task, solvers } => {
+            let solvers = if solvers.is_empty() {
+                String::new()
+            } else {
+                templates.render(
+                    &templates.task_solved_by,
+                    &[("solvers", &solvers.join(", "))],
+                )
+            };
+            message.content(templates.render(
+                &templates.task_solved_event,
+                &[("task", &task), ("ctf", &ctf), ("solvers", &solvers)],
+            ))
+        }
+        CtfnoteEvent::CtfCreated { ctf } => {
+            let (embed, buttons) = ctf_announcement(&ctf, &state.config);
+            let message = message.embed(embed).components(vec![buttons]);
+            channel_id.send_message(&state.http, message).await?;
+            // Don't let the announcement loop post it a second time.
+            state
+                .announced_ctfs
+                .update(|announced| announced.insert(ctf.id))
+                .await?;
+            return Ok(());
+        }
+        CtfnoteEvent::UserRegistered {
+            username,
+            discord_id,
+        } => {
+            let discord = discord_id.map_or_else(String::new, |id| {
+                templates.render(&templates.user_registered_discord, &[("user", &id)])
+            });
+            message.content(templates.render(
+                &templates.user_registered_event,
+                &[("username", &username), ("discord", &discord)],
+            ))
+        }
+    };
+    channel_id.send_message(&state.http, message).await?;
+    Ok(())
+}
+
+async fn handle(state: Arc<WebhookState>, request: Request<Body>) -> Response<Body> {
+    let status = match parse_event(&state.webhook, request).await {
+        Ok(event) => match post_event(&state, event).await {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(err) => {
+                error!("Failed to post CTFNote webhook event: {:?}", err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        },
+        Err(status) => status,
+    };
+
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+pub fn webhook_server(
+    config: Config,
+    ctx: serenity::Context,
+    announced_ctfs: Arc<JsonStore<HashSet<i32>>>,
+) {
+    let Some(webhook) = config.webhook.clone() else {
+        return;
+    };
+    let bind = webhook.bind;
+    let state = Arc::new(WebhookState {
+        config,
+        webhook,
+        http: ctx.http,
+        announced_ctfs,
+    });
+
+    tokio::spawn(async move {
+        let make_service = make_service_fn(move |_| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(state, request).await) }
+                }))
+            }
+        });
+
+        info!("Listening for CTFNote webhooks on {}", bind);
+        if let Err(err) = Server::bind(&bind).serve(make_service).await {
+            error!("CTFNote webhook server failed: {:?}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::AUTHORIZATION, Body, Method, Request, StatusCode};
+
+    use super::{parse_event, CtfnoteEvent};
+    use crate::WebhookConfig;
+
+    fn webhook() -> WebhookConfig {
+        WebhookConfig {
+            bind: ([127, 0, 0, 1], 0).into(),
+            secret: "hunter2".to_string(),
+            channel_id: 1,
+            task_channel_id: None,
+            ctf_channel_id: None,
+            user_channel_id: None,
+        }
+    }
+
+    fn build_request(
+        method: Method,
+        path: &str,
+        secret: Option<&str>,
+        body: &str,
+    ) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(secret) = secret {
+            request = request.header(AUTHORIZATION, format!("Bearer {secret}"));
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    const TASK_SOLVED: &str = r#"{"event": "task_solved", "ctf": "SIGINT CTF", "task": "baby-rev", "solvers": ["alice"]}"#;
+
+    async fn status(request: Request<Body>) -> StatusCode {
+        parse_event(&webhook(), request).await.err().unwrap()
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_path() {
+        let request = build_request(Method::POST, "/other", Some("hunter2"), TASK_SOLVED);
+        assert_eq!(status(request).await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_method() {
+        let request = build_request(Method::GET, "/webhook", Some("hunter2"), "");
+        assert_eq!(status(request).await, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_or_missing_secret() {
+        let request = build_request(Method::POST, "/webhook", Some("hunter3"), TASK_SOLVED);
+        assert_eq!(status(request).await, StatusCode::UNAUTHORIZED);
+        let request = build_request(Method::POST, "/webhook", None, TASK_SOLVED);
+        assert_eq!(status(request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_body() {
+        let request = build_request(Method::POST, "/webhook", Some("hunter2"), "{\"event\": ");
+        assert_eq!(status(request).await, StatusCode::BAD_REQUEST);
+        let request = build_request(
+            Method::POST,
+            "/webhook",
+            Some("hunter2"),
+            r#"{"event": "nope"}"#,
+        );
+        assert_eq!(status(request).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn parses_valid_events() {
+        let request = build_request(Method::POST, "/webhook", Some("hunter2"), TASK_SOLVED);
+        let Ok(CtfnoteEvent::TaskSolved { ctf, task, solvers }) =
+            parse_event(&webhook(), request).await
+        else {
+            panic!("expected a task_solved event");
+        };
+        assert_eq!(
+            (ctf.as_str(), task.as_str(), solvers),
+            ("SIGINT CTF", "baby-rev", vec!["alice".to_string()])
+        );
+
+        let body = r#"{"event": "user_registered", "username": "bob", "discord_id": "42"}"#;
+        let request = build_request(Method::POST, "/webhook", Some("hunter2"), body);
+        assert!(matches!(
+            parse_event(&webhook(), request).await,
+            Ok(CtfnoteEvent::UserRegistered { username, discord_id })
+                if username == "bob" && discord_id.as_deref() == Some("42")
+        ));
+    }
+
+    #[test]
+    fn routes_events_to_their_channels() {
+        let webhook = WebhookConfig {
+            task_channel_id: Some(2),
+            ..webhook()
+        };
+        let task = CtfnoteEvent::TaskCreated {
+            ctf: String::new(),
+            task: String::new(),
+        };
+        let user = CtfnoteEvent::UserRegistered {
+            username: String::new(),
+            discord_id: None,
+        };
+        assert_eq!(webhook.channel_for(&task).get(), 2);
+        assert_eq!(webhook.channel_for(&user).get(), 1);
+    }
+}